@@ -8,6 +8,16 @@ CREATE TABLE IF NOT EXISTS episodes (
     season_id INTEGER NOT NULL REFERENCES seasons(id) ON DELETE CASCADE,
     number INTEGER NOT NULL,
     title VARCHAR(255) NOT NULL,
+    air_date TEXT,
+    writers TEXT,
+    directors TEXT,
+    synopsis TEXT,
+    production_code TEXT,
+    metadata TEXT,
+    number_end INTEGER,
+    absolute_number INTEGER,
+    content_hash TEXT,
+    encoding TEXT,
     UNIQUE (season_id, number)
 );
 
@@ -21,11 +31,48 @@ CREATE TABLE IF NOT EXISTS lines (
     season_id INTEGER NOT NULL REFERENCES seasons(id) ON DELETE CASCADE,
     episode_id INTEGER NOT NULL REFERENCES episodes(id) ON DELETE CASCADE,
     speaker_id INTEGER REFERENCES speakers(id) ON DELETE SET NULL,
+    speaker_label TEXT,
     line_number INTEGER NOT NULL,
     content TEXT NOT NULL COLLATE NOCASE,
+    start_ms INTEGER,
+    end_ms INTEGER,
+    cue_settings TEXT,
+    style TEXT,
+    parenthetical TEXT,
+    scene_heading TEXT,
+    kind TEXT NOT NULL DEFAULT 'dialogue',
     CONSTRAINT unique_season_episode_line UNIQUE (season_id, episode_id, line_number)
 );
 
+CREATE TABLE IF NOT EXISTS line_speakers (
+    line_id INTEGER NOT NULL REFERENCES lines(id) ON DELETE CASCADE,
+    speaker_id INTEGER NOT NULL REFERENCES speakers(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (line_id, speaker_id)
+);
+
+CREATE TABLE IF NOT EXISTS scenes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    episode_id INTEGER NOT NULL REFERENCES episodes(id) ON DELETE CASCADE,
+    number INTEGER NOT NULL,
+    heading TEXT,
+    location TEXT,
+    first_line_number INTEGER NOT NULL,
+    last_line_number INTEGER NOT NULL,
+    UNIQUE (episode_id, number)
+);
+
+CREATE TABLE IF NOT EXISTS speaker_aliases (
+    alias VARCHAR(255) NOT NULL PRIMARY KEY COLLATE NOCASE,
+    name VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ingest_reports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    report TEXT NOT NULL
+);
+
 CREATE VIRTUAL TABLE IF NOT EXISTS lines_fts USING fts5(
     content,
     tokenize = 'porter unicode61'
@@ -37,19 +84,23 @@ CREATE TRIGGER IF NOT EXISTS lines_ai AFTER INSERT ON lines BEGIN
 END;
 
 CREATE TRIGGER IF NOT EXISTS lines_ad AFTER DELETE ON lines BEGIN
-    INSERT INTO lines_fts(lines_fts, rowid, content)
-    VALUES('delete', old.id, old.content);
+    DELETE FROM lines_fts WHERE rowid = old.id;
 END;
 
-CREATE TRIGGER IF NOT EXISTS lines_au AFTER UPDATE ON lines BEGIN
-    INSERT INTO lines_fts(lines_fts, rowid, content)
-    VALUES('delete', old.id, old.content);
+CREATE TRIGGER IF NOT EXISTS lines_au AFTER UPDATE OF content ON lines BEGIN
+    DELETE FROM lines_fts WHERE rowid = old.id;
     INSERT INTO lines_fts(rowid, content)
     VALUES (new.id, new.content);
 END;
 
 CREATE INDEX IF NOT EXISTS idx_episodes_season_id ON episodes(season_id);
+CREATE INDEX IF NOT EXISTS idx_episodes_absolute_number ON episodes(absolute_number);
+CREATE INDEX IF NOT EXISTS idx_episodes_air_date ON episodes(air_date);
+CREATE INDEX IF NOT EXISTS idx_episodes_content_hash ON episodes(content_hash);
 CREATE INDEX IF NOT EXISTS idx_lines_season_id ON lines(season_id);
 CREATE INDEX IF NOT EXISTS idx_lines_episode_id ON lines(episode_id);
 CREATE INDEX IF NOT EXISTS idx_lines_speaker_id ON lines(speaker_id);
-CREATE INDEX IF NOT EXISTS idx_lines_line_number ON lines(line_number);
\ No newline at end of file
+CREATE INDEX IF NOT EXISTS idx_lines_line_number ON lines(line_number);
+CREATE INDEX IF NOT EXISTS idx_lines_kind ON lines(kind);
+CREATE INDEX IF NOT EXISTS idx_line_speakers_speaker_id ON line_speakers(speaker_id);
+CREATE INDEX IF NOT EXISTS idx_speaker_aliases_name ON speaker_aliases(name);
\ No newline at end of file
@@ -1,21 +1,25 @@
-use crate::db::{setup_database, remove_cache};
-use crate::file_parser;
-use crate::models::{Episode, Line, RandomLineQuery, SearchPhrasesQuery, Season, Speaker, UserQuery};
+use crate::archive::{ArchiveError, UploadLimits, UploadSource};
+use crate::db::{is_valid_user_id, remove_cache, setup_database};
+use crate::file_parser::{self, IngestOptions, SpeakerDetector};
+use crate::jobs::{self, Cancelled, EditGuard, EditRegistry, Job, JobRegistry};
+use crate::speakers::{self, SpeakerError};
+use crate::ws::JobProgressSocket;
+use crate::models::{
+    Episode, IngestReportSummary, JobStatus, Line, LineKind, LoadedFile, MergeSpeakersRequest, RandomLineQuery, RenameSpeakerRequest, Scene,
+    SearchContext, SearchPhrasesQuery, Season, SpeakerAliasRequest, StoredIngestReport, TranscriptQuery, UserQuery,
+};
 use actix_multipart::Multipart;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse, Responder};
+use actix_web_actors::ws;
 use futures_util::stream::TryStreamExt;
 use regex::Regex;
 use sanitize_filename::sanitize;
 use serde_json::{json, Value};
 use sqlx::{SqlitePool, Row};
 use std::collections::HashMap;
-use std::io::{self, Read};
 use std::path::Path;
 use std::sync::Arc;
-use tokio::fs;
-use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
-use zip::ZipArchive;
 pub type DatabaseRegistry = Arc<Mutex<HashMap<String, SqlitePool>>>;
 
 
@@ -37,16 +41,60 @@ async fn get_db_pool(
     })
 }
 
+///Gets the user's database connection pool, setting up a new database if they don't have one yet
+async fn get_or_create_db_pool(
+    db_registry: &DatabaseRegistry,
+    schema_path: &str,
+    user_id: &str,
+) -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    if let Ok(pool) = get_db_pool(db_registry, Some(user_id)).await {
+        return Ok(pool);
+    }
+    let (pool, _) = setup_database(user_id, schema_path).await?;
+    db_registry.lock().await.insert(user_id.to_string(), pool.clone());
+    Ok(pool)
+}
+
+///Reserves an edit of the user's dataset, or returns None if they have an upload being processed.
+///The check happens under the job registry's lock and the guard is held until the edit is written, so an upload can't start in between
+async fn reserve_edit(job_registry: &JobRegistry, edit_registry: &EditRegistry, user_id: &str) -> Option<EditGuard> {
+    let registry = job_registry.lock().await;
+    if registry.values().any(|job| job.is_active() && job.status().user_id == user_id) {
+        return None;
+    }
+    Some(EditGuard::new(edit_registry, user_id))
+}
+
+///Adds a queued job for the user's upload, or returns None if they already have an upload being processed or an edit in progress.
+///The check and the insert happen under one lock, so two uploads sent at the same time can't both start
+async fn reserve_job(job_registry: &JobRegistry, edit_registry: &EditRegistry, user_id: &str) -> Option<Arc<Job>> {
+    let mut registry = job_registry.lock().await;
+    jobs::prune_finished(&mut registry);
+    if registry.values().any(|job| job.is_active() && job.status().user_id == user_id)
+        || edit_registry.lock().unwrap().contains_key(user_id)
+    {
+        return None;
+    }
+    let job = Arc::new(Job::new(user_id));
+    registry.insert(job.id(), job.clone());
+    Some(job)
+}
+
 ///Removes a user's database from the registry and deletes the db file
 async fn cleanup(
     db_registry: web::Data<DatabaseRegistry>,
     user_id: &str,
 ) -> HttpResponse {
+    if !is_valid_user_id(user_id) {
+        return HttpResponse::BadRequest().body("Invalid user_id");
+    }
     let removed = {
         let mut registry = db_registry.lock().await;
         registry.remove(user_id)
     };
 
+    remove_cache(user_id).await;
+
     let db_path = format!("./temp_dbs/{}.sqlite", user_id);
     if let Err(err) = tokio::fs::remove_file(&db_path).await {
         if err.kind() != std::io::ErrorKind::NotFound {
@@ -62,69 +110,188 @@ async fn cleanup(
     }
 }
 
-///Ensures zip file is "valid" or not empty
-fn verify_zip_file(path: &str) -> io::Result<()> {
-    let file = std::fs::File::open(path)?;
-    let archive = ZipArchive::new(file)?;
-    if archive.len() == 0 {
-        Err(io::Error::new(io::ErrorKind::InvalidData, "ZIP file is empty"))
-    } else {
-        Ok(())
-    }
+///Checks if an uploaded file is a JSON/JSONL or CSV/TSV transcript rather than a ZIP archive
+fn is_structured_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["json", "jsonl", "csv", "tsv"].iter().any(|structured| ext.eq_ignore_ascii_case(structured)))
 }
 
-///Opens the given zip file, and extracts all text files to the output directory
-async fn extract_zip(zip_path: &str, output_dir: &str) -> io::Result<()> {
-    let file = std::fs::File::open(zip_path)?;
-    let mut archive = ZipArchive::new(file)?;
+///Builds the SQL condition for a comma separated list of line kinds like "dialogue,stage_direction", with the error response if one isn't a kind.
+///The kinds are written into the SQL rather than bound, which is safe since they only come from LineKind
+fn kind_condition(kinds: Option<&str>) -> Result<Option<String>, HttpResponse> {
+    let Some(kinds) = kinds.filter(|kinds| !kinds.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let mut values = Vec::new();
+    for kind in kinds.split(',') {
+        let kind: LineKind = serde_json::from_value(Value::String(kind.trim().to_string())).map_err(|err| {
+            HttpResponse::BadRequest().json(json!({"error": format!("Invalid line kind: {}", err)}))
+        })?;
+        values.push(format!("'{}'", kind.as_str()));
+    }
+    Ok(Some(format!("l.kind IN ({})", values.join(", "))))
+}
 
-    fs::create_dir_all(output_dir).await?;
-    for i in 0..archive.len() {
-        let mut zip_file = archive.by_index(i)?;
-        let outpath = Path::new(output_dir).join(zip_file.name());
-        if let Some(parent) = outpath.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-        if !zip_file.name().ends_with(".txt") {
+///Builds the SQL conditions and values for an air date range, with the error response if a date isn't formatted as YYYY-MM-DD.
+///Both ends are included, and lines of episodes without an air date are left out once either end is given
+fn air_date_conditions(aired_after: Option<&str>, aired_before: Option<&str>) -> Result<Vec<(&'static str, String)>, HttpResponse> {
+    let mut conditions = Vec::new();
+    for (name, condition, date) in [("aired_after", "e.air_date >= ?", aired_after), ("aired_before", "e.air_date <= ?", aired_before)] {
+        let Some(date) = date.map(str::trim).filter(|date| !date.is_empty()) else {
             continue;
+        };
+        if !file_parser::is_date(date) {
+            return Err(HttpResponse::BadRequest().json(json!({"error": format!("{} must be formatted as YYYY-MM-DD", name)})));
         }
-        let mut outfile = fs::File::create(&outpath).await?;
-        let mut buffer = vec![0; 8192];
-        loop {
-            let bytes_read = zip_file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
-            }
-            outfile.write_all(&buffer[..bytes_read]).await?;
+        conditions.push((condition, date.to_string()));
+    }
+    Ok(conditions)
+}
+
+///Reads a small multipart field into memory, counting it towards the upload size limit. Returns None if the limit is passed
+async fn read_field(
+    field: &mut actix_multipart::Field,
+    uploaded_bytes: &mut u64,
+    limit: u64,
+) -> Result<Option<Vec<u8>>, actix_web::Error> {
+    let mut contents = Vec::new();
+    while let Some(chunk) = field.try_next().await? {
+        *uploaded_bytes += chunk.len() as u64;
+        if *uploaded_bytes > limit {
+            return Ok(None);
+        }
+        contents.extend_from_slice(&chunk);
+    }
+    Ok(Some(contents))
+}
+
+///Reads the speaker detection rules sent with an upload as a JSON object, with the error response if they are invalid
+fn parse_speaker_rules(rules: &[u8]) -> Result<SpeakerDetector, HttpResponse> {
+    serde_json::from_slice(rules)
+        .map_err(|err| err.to_string())
+        .and_then(|rules| SpeakerDetector::new(rules).map_err(|err| err.to_string()))
+        .map_err(|err| HttpResponse::BadRequest().json(json!({"error": format!("Invalid speaker rules: {}", err)})))
+}
+
+///Builds the error response for a refused speaker change
+fn speaker_error_response(err: &SpeakerError) -> HttpResponse {
+    match err {
+        SpeakerError::NotFound(_) => HttpResponse::NotFound().json(json!({"error": err.to_string()})),
+        SpeakerError::NameTaken(_) | SpeakerError::AliasTaken { .. } => HttpResponse::Conflict().json(json!({"error": err.to_string()})),
+        SpeakerError::Invalid(_) => HttpResponse::BadRequest().json(json!({"error": err.to_string()})),
+        SpeakerError::Database(_) => {
+            eprintln!("Error updating speakers: {}", err);
+            HttpResponse::InternalServerError().json(json!({"error": "Error updating speakers"}))
         }
     }
-    Ok(())
 }
 
-///Gets the 2 lines before and after the given line (this is useful for frontend to see the context of the search result)
-async fn get_context_lines(db_pool: &SqlitePool, line: &Line) -> Vec<Line> {
-    let context_query = r#"
+///Builds the error response for a rejected upload, with the violation's code and the offending entry
+fn archive_error_response(err: &ArchiveError) -> HttpResponse {
+    let mut response = match err {
+        ArchiveError::UploadTooLarge { .. } | ArchiveError::UncompressedTooLarge { .. } => HttpResponse::PayloadTooLarge(),
+        ArchiveError::Io(_) => HttpResponse::InternalServerError(),
+        _ => HttpResponse::BadRequest(),
+    };
+    response.json(json!({"error": err.to_string(), "code": err.code(), "entry": err.entry()}))
+}
+
+///Column for the `speakers` of a line, all of its speakers in order as a JSON array
+fn line_speakers_column(line_id: &str) -> String {
+    format!(
+        r#"(SELECT json_group_array(json_object('id', id, 'name', name)) FROM (
+            SELECT sp.id, sp.name FROM line_speakers ls
+            JOIN speakers sp ON ls.speaker_id = sp.id
+            WHERE ls.line_id = {}
+            ORDER BY ls.position
+        )) AS speakers"#,
+        line_id
+    )
+}
+
+///Selects scenes with the speakers that have lines in them, in the order they first speak
+const SCENE_SQL: &str = r#"
+    SELECT
+        sc.id,
+        sc.episode_id,
+        sc.number,
+        sc.heading,
+        sc.location,
+        sc.first_line_number,
+        sc.last_line_number,
+        (SELECT json_group_array(json_object('id', id, 'name', name)) FROM (
+            SELECT sp.id, sp.name FROM lines l
+            JOIN line_speakers ls ON ls.line_id = l.id
+            JOIN speakers sp ON ls.speaker_id = sp.id
+            WHERE l.episode_id = sc.episode_id AND l.line_number BETWEEN sc.first_line_number AND sc.last_line_number
+            GROUP BY sp.id
+            ORDER BY MIN(l.line_number), MIN(ls.position)
+        )) AS speakers
+    FROM scenes sc
+"#;
+
+///Gets an episode's lines from one line number to another
+async fn lines_between(db_pool: &SqlitePool, episode_id: i64, first_line_number: i32, last_line_number: i32) -> Result<Vec<Line>, sqlx::Error> {
+    let lines_query = format!(
+        r#"
         SELECT 
             lines.id,
             lines.season_id,
             lines.episode_id,
             lines.speaker_id,
             speakers.name AS speaker_name,
+            lines.speaker_label,
             lines.line_number,
-            lines.content
+            lines.content,
+            lines.start_ms,
+            lines.end_ms,
+            lines.cue_settings,
+            lines.style,
+            lines.parenthetical,
+            lines.scene_heading,
+            lines.kind,
+            {}
         FROM lines
         LEFT JOIN speakers ON lines.speaker_id = speakers.id
         WHERE lines.episode_id = ?
         AND lines.line_number BETWEEN ? AND ?
         ORDER BY lines.line_number ASC
-    "#;
+    "#,
+        line_speakers_column("lines.id")
+    );
 
-    sqlx::query_as(context_query)
-        .bind(line.episode_id)
-        .bind(line.line_number.saturating_sub(2).max(1))
-        .bind(line.line_number.saturating_add(2))
+    sqlx::query_as(&lines_query)
+        .bind(episode_id)
+        .bind(first_line_number)
+        .bind(last_line_number)
         .fetch_all(db_pool)
         .await
+}
+
+///Gets the lines around a search result (this is useful for frontend to see the context of the search result),
+///either the 2 lines before and after it or every line of its scene. A line outside of any scene gets the 2 lines before and after
+async fn get_context_lines(db_pool: &SqlitePool, line: &Line, context: SearchContext) -> Vec<Line> {
+    let scene_range: Option<(i32, i32)> = match context {
+        SearchContext::Lines => None,
+        SearchContext::Scene => sqlx::query_as(
+            "SELECT first_line_number, last_line_number FROM scenes WHERE episode_id = ?1 AND first_line_number <= ?2 AND last_line_number >= ?2 ORDER BY number DESC LIMIT 1",
+        )
+        .bind(line.episode_id)
+        .bind(line.line_number)
+        .fetch_optional(db_pool)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Scene query failed: {}", err);
+            None
+        }),
+    };
+    let (first_line_number, last_line_number) =
+        scene_range.unwrap_or((line.line_number.saturating_sub(2).max(1), line.line_number.saturating_add(2)));
+
+    lines_between(db_pool, line.episode_id, first_line_number, last_line_number)
+        .await
         .unwrap_or_else(|err| {
             eprintln!("Context query failed: {}", err);
             Vec::new()
@@ -144,80 +311,224 @@ async fn cleanup_db(
     }
 }
 
-///Endpoint to handle file uploads
-#[post("/upload")]
-async fn upload_zip(
-    mut payload: Multipart,
-    db_registry: web::Data<DatabaseRegistry>,
-    schema_path: web::Data<String>,
-) -> Result<HttpResponse, actix_web::Error> {
-    let user_id = "default".to_string();
+///Loads the transcripts in an upload into the user's database, and records the outcome on the job.
+///In replace mode the dataset is cleared first, in merge mode the uploaded episodes are added to it or replace the same episodes
+async fn run_upload_job(
+    job: Arc<Job>,
+    db_registry: DatabaseRegistry,
+    schema_path: String,
+    user_id: String,
+    mut source: UploadSource,
+    options: IngestOptions,
+) {
+    job.set_running();
 
-    //Makes a temporary directory to extract files to
-    let temp_dir = "./temp_uploads";
-    let extract_dir_path = format!("{}/{}", temp_dir, user_id);
-    let extract_dir = Path::new(&extract_dir_path);
+    //Sets up database connection. A replace upload deletes the old episodes in its own transaction, so they are only gone once the new ones are committed
+    let db_pool = match get_or_create_db_pool(&db_registry, &schema_path, &user_id).await {
+        Ok(pool) => pool,
+        Err(err) => {
+            eprintln!("Failed to setup database: {}", err);
+            job.fail(vec!["Failed to setup database".to_string()]);
+            return;
+        }
+    };
 
-    fs::create_dir_all(&extract_dir).await.map_err(|_| {
-        actix_web::error::ErrorInternalServerError("Failed to create directory")
-    })?;
-    {
-        let mut registry = db_registry.lock().await;
-        registry.remove(&user_id);
-        let _ = fs::remove_file(format!("./temp_dbs/{}.sqlite", user_id)).await;
+    //processes the transcript files
+    let result = file_parser::process_seasons(&db_pool, &mut source, &user_id, &options, &job).await;
+
+    match result {
+        Ok(report) => {
+            //Saves the report so it can be looked up again after the upload
+            let report_id = match sqlx::query_scalar::<_, i64>("INSERT INTO ingest_reports (report) VALUES (?) RETURNING id")
+                .bind(sqlx::types::Json(&report))
+                .fetch_one(&db_pool)
+                .await
+            {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    eprintln!("Failed to save ingest report: {}", err);
+                    None
+                }
+            };
+            job.complete(report_id, report);
+        }
+        Err(e) if e.is::<Cancelled>() => job.mark_cancelled(),
+        Err(e) => match e.downcast_ref::<file_parser::ValidationErrors>() {
+            Some(errors) => job.fail(errors.0.clone()),
+            None => job.fail(vec![e.to_string()]),
+        },
     }
-    remove_cache(&user_id).await;
-    let mut saved_file_path = String::new();
+}
+
+///Reads an upload's file and form fields into memory, with the error response if the upload is rejected.
+///The upload is kept in memory and read straight from there, so concurrent uploads never share files on disk
+async fn read_upload(payload: &mut Multipart, limits: &UploadLimits) -> Result<(UploadSource, IngestOptions), HttpResponse> {
+    let mut file_name = String::new();
+    let mut contents = Vec::new();
+    let mut options = IngestOptions::default();
+    let mut uploaded_bytes: u64 = 0;
 
     while let Some(mut field) = payload.try_next().await.map_err(|_| {
-        actix_web::error::ErrorInternalServerError("Failed to process upload")
+        HttpResponse::InternalServerError().body("Failed to process upload")
     })? {
         if field.content_disposition().and_then(|cd| cd.get_name()) == Some("file") {
             let filename = field
                 .content_disposition()
                 .and_then(|cd| cd.get_filename())
-                .ok_or_else(|| actix_web::error::ErrorBadRequest("No filename provided"))?;
-            saved_file_path = format!("{}/{}", temp_dir, sanitize(filename));
-            let mut f = fs::File::create(&saved_file_path).await?;
-            while let Some(chunk) = field.try_next().await? {
-                f.write_all(&chunk).await?;
+                .ok_or_else(|| HttpResponse::BadRequest().body("No filename provided"))?;
+            file_name = sanitize(filename);
+            while let Some(chunk) = field.try_next().await.map_err(HttpResponse::from_error)? {
+                //Stops reading as soon as the upload goes over the limit, rather than buffering it all first
+                uploaded_bytes += chunk.len() as u64;
+                if uploaded_bytes > limits.max_upload_bytes {
+                    return Err(archive_error_response(&ArchiveError::UploadTooLarge { limit: limits.max_upload_bytes }));
+                }
+                contents.extend_from_slice(&chunk);
             }
-            f.flush().await?;
-            drop(f);
+        } else if field.content_disposition().and_then(|cd| cd.get_name()) == Some("mapping") {
+            //Column mapping for CSV/TSV transcripts, sent as a JSON object
+            let Some(mapping) = read_field(&mut field, &mut uploaded_bytes, limits.max_upload_bytes).await.map_err(HttpResponse::from_error)? else {
+                return Err(archive_error_response(&ArchiveError::UploadTooLarge { limit: limits.max_upload_bytes }));
+            };
+            options.csv_mapping = match serde_json::from_slice(&mapping) {
+                Ok(mapping) => mapping,
+                Err(err) => {
+                    return Err(HttpResponse::BadRequest().json(json!({"error": format!("Invalid column mapping: {}", err)})));
+                }
+            };
+        } else if field.content_disposition().and_then(|cd| cd.get_name()) == Some("speaker_rules") {
+            //How speaker labels are detected and normalized in transcript files, sent as a JSON object
+            let Some(rules) = read_field(&mut field, &mut uploaded_bytes, limits.max_upload_bytes).await.map_err(HttpResponse::from_error)? else {
+                return Err(archive_error_response(&ArchiveError::UploadTooLarge { limit: limits.max_upload_bytes }));
+            };
+            options.speaker_rules = Arc::new(parse_speaker_rules(&rules)?);
+        } else if field.content_disposition().and_then(|cd| cd.get_name()) == Some("mode") {
+            //"replace" (the default) wipes the dataset first, "merge" adds or replaces only the uploaded episodes
+            let Some(mode) = read_field(&mut field, &mut uploaded_bytes, limits.max_upload_bytes).await.map_err(HttpResponse::from_error)? else {
+                return Err(archive_error_response(&ArchiveError::UploadTooLarge { limit: limits.max_upload_bytes }));
+            };
+            let mode = String::from_utf8_lossy(&mode).trim().to_string();
+            options.mode = match serde_json::from_value(Value::String(mode)) {
+                Ok(mode) => mode,
+                Err(err) => {
+                    return Err(HttpResponse::BadRequest().json(json!({"error": format!("Invalid upload mode: {}", err)})));
+                }
+            };
+        } else if field.content_disposition().and_then(|cd| cd.get_name()) == Some("reprocess") {
+            //"true" loads files again even if their episode was already loaded from the same file, in both upload modes
+            let Some(reprocess) = read_field(&mut field, &mut uploaded_bytes, limits.max_upload_bytes).await.map_err(HttpResponse::from_error)? else {
+                return Err(archive_error_response(&ArchiveError::UploadTooLarge { limit: limits.max_upload_bytes }));
+            };
+            options.reprocess = match String::from_utf8_lossy(&reprocess).trim().parse() {
+                Ok(reprocess) => reprocess,
+                Err(_) => return Err(HttpResponse::BadRequest().json(json!({"error": "reprocess must be true or false"}))),
+            };
         }
     }
-    if saved_file_path.is_empty() {
-        return Ok(HttpResponse::BadRequest().json(json!({"error": "No file uploaded"})));
-    }
-    if let Err(err) = verify_zip_file(&saved_file_path) {
-        return Ok(HttpResponse::BadRequest().json(json!({"error": format!("Invalid ZIP file: {}", err)})));
-    }
-    if let Err(err) = extract_zip(&saved_file_path, extract_dir.to_str().unwrap_or(""))
-        .await
-    {
-        return Ok(HttpResponse::InternalServerError().json(json!({"error": err.to_string()})));
+    if file_name.is_empty() {
+        return Err(HttpResponse::BadRequest().json(json!({"error": "No file uploaded"})));
     }
 
-    //Sets up database connection
-    let db_pool = match get_db_pool(&db_registry, Some("default")).await {
-        Ok(pool) => pool,
-        Err(_) => {
-            let (pool, _) = setup_database("default", schema_path.get_ref()).await.map_err(|_| {
-                actix_web::error::ErrorInternalServerError("Failed to setup database")
-            })?;
-            db_registry.lock().await.insert("default".to_string(), pool.clone());
-            pool
+    //A single JSON/JSONL or CSV/TSV transcript can be uploaded without zipping it
+    let source = if is_structured_file(&file_name) {
+        UploadSource::file(&file_name, contents)
+    } else {
+        UploadSource::zip(contents, limits).map_err(|err| archive_error_response(&err))?
+    };
+    Ok((source, options))
+}
+
+///Endpoint to handle file uploads, the upload is processed in the background and the response has the job ID to poll.
+///Files whose episode was already loaded from the same contents are only skipped in merge mode, a replace upload loads every file
+#[post("/upload")]
+async fn upload_zip(
+    mut payload: Multipart,
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    schema_path: web::Data<String>,
+    limits: web::Data<UploadLimits>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = "default".to_string();
+
+    //Only one upload per user can be processed at a time, and not while an edit is being written, so the slot is reserved before the body is read
+    let Some(job) = reserve_job(&job_registry, &edit_registry, &user_id).await else {
+        return Ok(HttpResponse::Conflict().json(json!({"error": "An upload or edit is already being processed"})));
+    };
+
+    let upload = read_upload(&mut payload, &limits).await;
+    let (source, options) = match upload {
+        Ok(upload) => upload,
+        Err(response) => {
+            //Frees the reserved slot, since the upload never started
+            job_registry.lock().await.remove(&job.id());
+            return Ok(response);
         }
-    };    
+    };
 
-    //processes the text files
-    let result = file_parser::process_seasons(&db_pool, extract_dir, &user_id).await;
-    fs::remove_file(&saved_file_path).await.ok();
-    fs::remove_dir_all(&extract_dir).await.ok();
+    let job_id = job.id();
+    actix_web::rt::spawn(run_upload_job(
+        job,
+        db_registry.get_ref().clone(),
+        schema_path.get_ref().clone(),
+        user_id,
+        source,
+        options,
+    ));
 
-    match result {
-        Ok(_) => Ok(HttpResponse::Ok().json(json!({"message": "Upload and processing successful"}))),
-        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({"error": e.to_string()}))),
+    Ok(HttpResponse::Accepted().json(json!({"message": "Upload accepted for processing", "job_id": job_id})))
+}
+
+///Endpoint to list a user's ingest jobs
+#[get("/jobs")]
+async fn get_jobs(job_registry: web::Data<JobRegistry>, user_query: web::Query<UserQuery>) -> impl Responder {
+    let jobs: Vec<JobStatus> = job_registry
+        .lock()
+        .await
+        .values()
+        .map(|job| job.status())
+        .filter(|status| status.user_id == user_query.user_id)
+        .collect();
+    HttpResponse::Ok().json(jobs)
+}
+
+///Endpoint to get an ingest job's status, progress, errors and final report
+#[get("/jobs/{job_id}")]
+async fn get_job(
+    job_registry: web::Data<JobRegistry>,
+    job_id: web::Path<String>,
+) -> impl Responder {
+    match job_registry.lock().await.get(job_id.as_str()) {
+        Some(job) => HttpResponse::Ok().json(job.status()),
+        None => HttpResponse::NotFound().json(json!({"error": "Job not found"})),
+    }
+}
+
+///Endpoint to follow an ingest job's progress over a WebSocket, the current status is sent first and then each event as it happens
+#[get("/jobs/{job_id}/ws")]
+async fn job_progress_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    job_registry: web::Data<JobRegistry>,
+    job_id: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let job = job_registry.lock().await.get(job_id.as_str()).cloned();
+    match job {
+        Some(job) => ws::start(JobProgressSocket::new(job), &req, stream),
+        None => Ok(HttpResponse::NotFound().json(json!({"error": "Job not found"}))),
+    }
+}
+
+///Endpoint to cancel a running ingest job, its transaction is rolled back so no partial data is kept
+#[post("/jobs/{job_id}/cancel")]
+async fn cancel_job(
+    job_registry: web::Data<JobRegistry>,
+    job_id: web::Path<String>,
+) -> impl Responder {
+    match job_registry.lock().await.get(job_id.as_str()) {
+        Some(job) if job.cancel() => HttpResponse::Accepted().json(json!({"message": "Cancellation requested"})),
+        Some(_) => HttpResponse::Conflict().json(json!({"error": "Job has already finished"})),
+        None => HttpResponse::NotFound().json(json!({"error": "Job not found"})),
     }
 }
 
@@ -240,6 +551,14 @@ async fn search_phrases(
         format!("MATCH {}", escape_fts5_query(&phrase))
     };
 
+    let kind_filter = match kind_condition(query.kind.as_deref()) {
+        Ok(kind_filter) => kind_filter,
+        Err(resp) => return resp,
+    };
+    let air_dates = match air_date_conditions(query.aired_after.as_deref(), query.aired_before.as_deref()) {
+        Ok(air_dates) => air_dates,
+        Err(resp) => return resp,
+    };
     let mut conditions = Vec::new();
     let mut params: Vec<String> = Vec::new();
     if !phrase_query.is_empty() {
@@ -255,9 +574,16 @@ async fn search_phrases(
         params.push(episode.to_string());
     }
     if let Some(speaker) = query.speaker {
-        conditions.push("l.speaker_id = ?");
+        conditions.push("EXISTS (SELECT 1 FROM line_speakers ls WHERE ls.line_id = l.id AND ls.speaker_id = ?)");
         params.push(speaker.to_string());
     }
+    if let Some(kind_filter) = &kind_filter {
+        conditions.push(kind_filter);
+    }
+    for (condition, date) in air_dates {
+        conditions.push(condition);
+        params.push(date);
+    }
     let where_clause = if conditions.is_empty() {
         String::new()
     } else {
@@ -271,9 +597,18 @@ async fn search_phrases(
             l.season_id, 
             l.episode_id, 
             l.speaker_id, 
-            s.name AS speaker_name, 
+            s.name AS speaker_name,
+            l.speaker_label,
             l.line_number,  
-            l.content
+            l.content,
+            l.start_ms,
+            l.end_ms,
+            l.cue_settings,
+            l.style,
+            l.parenthetical,
+            l.scene_heading,
+            l.kind,
+            {}
         FROM lines l
         JOIN lines_fts fts ON l.id = fts.rowid
         LEFT JOIN speakers s ON l.speaker_id = s.id
@@ -285,6 +620,7 @@ async fn search_phrases(
             e.number ASC,
             l.line_number ASC
         "#,
+        line_speakers_column("l.id"),
         where_clause
     );
 
@@ -305,7 +641,7 @@ async fn search_phrases(
     }
     let mut response_data = Vec::new();
     for line in results.iter() {
-        let context_lines = get_context_lines(&db_pool, line).await;
+        let context_lines = get_context_lines(&db_pool, line, query.context).await;
         response_data.push((line.clone(), context_lines));
     }
     HttpResponse::Ok().json(response_data)
@@ -321,7 +657,7 @@ async fn get_random_line(
         Ok(pool) => pool,
         Err(resp) => return resp,
     };
-    let mut sql = String::from(
+    let mut sql = format!(
         r#"
         SELECT 
             l.id, 
@@ -329,15 +665,33 @@ async fn get_random_line(
             l.episode_id,
             l.speaker_id,
             s.name AS speaker_name,
+            l.speaker_label,
             l.line_number,
-            l.content
+            l.content,
+            l.start_ms,
+            l.end_ms,
+            l.cue_settings,
+            l.style,
+            l.parenthetical,
+            l.scene_heading,
+            l.kind,
+            {}
         FROM lines l
         LEFT JOIN speakers s ON l.speaker_id = s.id
         JOIN episodes e ON l.episode_id = e.id
         JOIN seasons sn ON e.season_id = sn.id
         "#,
+        line_speakers_column("l.id")
     );
 
+    let kind_filter = match kind_condition(query.kind.as_deref()) {
+        Ok(kind_filter) => kind_filter,
+        Err(resp) => return resp,
+    };
+    let air_dates = match air_date_conditions(query.aired_after.as_deref(), query.aired_before.as_deref()) {
+        Ok(air_dates) => air_dates,
+        Err(resp) => return resp,
+    };
     let mut conditions = Vec::new();
     let mut binds = Vec::new();
     if let Some(season) = query.season {
@@ -349,9 +703,16 @@ async fn get_random_line(
         binds.push(episode);
     }
     if let Some(speaker) = query.speaker {
-        conditions.push("l.speaker_id = ?");
+        conditions.push("EXISTS (SELECT 1 FROM line_speakers ls WHERE ls.line_id = l.id AND ls.speaker_id = ?)");
         binds.push(speaker);
     }
+    if let Some(kind_filter) = &kind_filter {
+        conditions.push(kind_filter);
+    }
+    //The dates are bound after the numbers, so their conditions go last
+    for (condition, _) in &air_dates {
+        conditions.push(condition);
+    }
     if !conditions.is_empty() {
         sql.push_str(" WHERE ");
         sql.push_str(&conditions.join(" AND "));
@@ -361,6 +722,9 @@ async fn get_random_line(
     for value in binds {
         query_builder = query_builder.bind(value);
     }
+    for (_, date) in air_dates {
+        query_builder = query_builder.bind(date);
+    }
     match query_builder.fetch_optional(&db_pool).await {
         Ok(Some(line)) => HttpResponse::Ok().json(line),
         Ok(None) => {
@@ -374,17 +738,102 @@ async fn get_random_line(
     }
 }
 
-///Endpoint to get an episode's transcript consisting of speakers + their lines, using the season + episode numbers
+///Gets an episode's lines, keeping only the kinds in the filter
+async fn transcript_response(db_pool: &SqlitePool, episode_id: i64, kind_filter: Option<String>) -> HttpResponse {
+    let query = format!(
+        r#"
+    SELECT 
+        l.id, 
+        l.season_id, 
+        l.episode_id, 
+        l.speaker_id, 
+        s.name AS speaker_name,
+        l.speaker_label,
+        l.line_number, 
+        l.content,
+        l.start_ms,
+        l.end_ms,
+        l.cue_settings,
+        l.style,
+        l.parenthetical,
+        l.scene_heading,
+        l.kind,
+        {}
+    FROM lines l
+    LEFT JOIN speakers s ON l.speaker_id = s.id
+    WHERE l.episode_id = ? {}
+    ORDER BY l.line_number
+    "#,
+        line_speakers_column("l.id"),
+        kind_filter.map(|kind_filter| format!("AND {}", kind_filter)).unwrap_or_default()
+    );
+
+    let transcript = sqlx::query_as::<_, Line>(&query)
+        .bind(episode_id)
+        .fetch_all(db_pool)
+        .await;
+
+    match transcript {
+        Ok(lines) => HttpResponse::Ok().json(lines),
+        Err(err) => {
+            eprintln!("Error fetching transcript: {}", err);
+            HttpResponse::InternalServerError().body("Error fetching transcript")
+        }
+    }
+}
+
+///Endpoint to get an episode's transcript consisting of speakers + their lines, using its absolute episode number
+#[get("/transcripts/absolute/{absolute_number}")]
+async fn get_transcript_by_absolute_number(
+    db_registry: web::Data<DatabaseRegistry>,
+    path: web::Path<i32>,
+    user_query: web::Query<UserQuery>,
+    transcript_query: web::Query<TranscriptQuery>,
+) -> impl Responder {
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    let kind_filter = match kind_condition(transcript_query.kind.as_deref()) {
+        Ok(kind_filter) => kind_filter,
+        Err(resp) => return resp,
+    };
+
+    let absolute_number = path.into_inner();
+    let episode_id: Option<i64> = match sqlx::query_scalar("SELECT id FROM episodes WHERE absolute_number = ? ORDER BY id LIMIT 1")
+        .bind(absolute_number)
+        .fetch_optional(&db_pool)
+        .await
+    {
+        Ok(episode_id) => episode_id,
+        Err(err) => {
+            eprintln!("Error checking episode existence: {}", err);
+            return HttpResponse::InternalServerError().body("Error getting episode, does not exist");
+        }
+    };
+    match episode_id {
+        Some(episode_id) => transcript_response(&db_pool, episode_id, kind_filter).await,
+        None => HttpResponse::NotFound().body(format!("Episode with absolute number {} not found", absolute_number)),
+    }
+}
+
+///Endpoint to get an episode's transcript consisting of speakers + their lines, using the season + episode numbers.
+///Any number in a multi-part episode like 3x01-02 gets the whole episode
 #[get("/transcripts/{season_num}/{episode_num}")]
 async fn get_transcript(
     db_registry: web::Data<DatabaseRegistry>,
     path: web::Path<(i64, i32)>,
     user_query: web::Query<UserQuery>,
+    transcript_query: web::Query<TranscriptQuery>,
 ) -> impl Responder {
     let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
         Ok(pool) => pool,
         Err(resp) => return resp,
     };
+    let kind_filter = match kind_condition(transcript_query.kind.as_deref()) {
+        Ok(kind_filter) => kind_filter,
+        Err(resp) => return resp,
+    };
     
     let (season_num, episode_num) = path.into_inner();
     let season_exists: i64 = match sqlx::query("SELECT EXISTS(SELECT 1 FROM seasons WHERE number = ?)")
@@ -401,47 +850,148 @@ async fn get_transcript(
     if season_exists == 0 {
         return HttpResponse::NotFound().body(format!("Season {} not found", season_num));
     }
-    let episode_exists: i64 = match sqlx::query("SELECT EXISTS(SELECT 1 FROM episodes WHERE number = ?)")
-        .bind(episode_num)
-        .fetch_one(&db_pool)
-        .await
+    let episode_id: Option<i64> = match sqlx::query_scalar(
+        "SELECT e.id FROM episodes e JOIN seasons sn ON e.season_id = sn.id WHERE sn.number = ?1 AND e.number <= ?2 AND COALESCE(e.number_end, e.number) >= ?2 ORDER BY e.number DESC LIMIT 1",
+    )
+    .bind(season_num)
+    .bind(episode_num)
+    .fetch_optional(&db_pool)
+    .await
     {
-        Ok(row) => row.get::<i64, _>(0),
+        Ok(episode_id) => episode_id,
         Err(err) => {
             eprintln!("Error checking episode existence: {}", err);
             return HttpResponse::InternalServerError().body("Error getting episode, does not exist");
         }
     };
-    if episode_exists == 0 {
-        return HttpResponse::NotFound().body(format!("Episode {} not found", episode_num));
+    match episode_id {
+        Some(episode_id) => transcript_response(&db_pool, episode_id, kind_filter).await,
+        None => HttpResponse::NotFound().body(format!("Episode {} not found", episode_num)),
     }
-    let query = r#"
-    SELECT 
-        l.id, 
-        l.season_id, 
-        l.episode_id, 
-        l.speaker_id, 
-        s.name AS speaker_name, 
-        l.line_number, 
-        l.content
-    FROM lines l
-    LEFT JOIN speakers s ON l.speaker_id = s.id
-    JOIN episodes e ON l.episode_id = e.id
-    JOIN seasons sn ON e.season_id = sn.id
-    WHERE sn.number = ? AND e.number = ?
-    "#;
+}
 
-    let transcript = sqlx::query_as::<_, Line>(query)
-        .bind(season_num)
-        .bind(episode_num)
-        .fetch_all(&db_pool)
-        .await;
+///Endpoint to replace an episode's transcript with a single uploaded file, adding the season and episode if they don't exist yet
+///The multipart form has the transcript in `file`, an optional `title` and optional `speaker_rules`
+#[put("/transcripts/{season_num}/{episode_num}")]
+async fn put_transcript(
+    mut payload: Multipart,
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    limits: web::Data<UploadLimits>,
+    path: web::Path<(i32, i32)>,
+    user_query: web::Query<UserQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (season_num, episode_num) = path.into_inner();
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return Ok(HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"})));
+    };
 
-    match transcript {
-        Ok(lines) => HttpResponse::Ok().json(lines),
+    let mut file_name = String::new();
+    let mut contents = Vec::new();
+    let mut title = None;
+    let mut speaker_rules = SpeakerDetector::default();
+    let mut uploaded_bytes: u64 = 0;
+    while let Some(mut field) = payload.try_next().await.map_err(|_| {
+        actix_web::error::ErrorInternalServerError("Failed to process upload")
+    })? {
+        let name = field.content_disposition().and_then(|cd| cd.get_name()).map(str::to_string);
+        if name.as_deref() == Some("file") {
+            file_name = field
+                .content_disposition()
+                .and_then(|cd| cd.get_filename())
+                .map(sanitize)
+                .ok_or_else(|| actix_web::error::ErrorBadRequest("No filename provided"))?;
+        }
+        let Some(value) = read_field(&mut field, &mut uploaded_bytes, limits.max_upload_bytes).await? else {
+            return Ok(archive_error_response(&ArchiveError::UploadTooLarge { limit: limits.max_upload_bytes }));
+        };
+        match name.as_deref() {
+            Some("file") => contents = value,
+            Some("title") => title = Some(String::from_utf8_lossy(&value).trim().to_string()),
+            Some("speaker_rules") => match parse_speaker_rules(&value) {
+                Ok(rules) => speaker_rules = rules,
+                Err(response) => return Ok(response),
+            },
+            _ => {}
+        }
+    }
+    if file_name.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({"error": "No file uploaded"})));
+    }
+    if is_structured_file(&file_name) || !file_parser::is_supported_file(&file_name) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Only transcript files (.txt, .srt, .vtt, .ass, .ssa, .fountain) can replace a single episode, structured files carry their own episode numbers so upload them with mode=merge instead"
+        })));
+    }
+
+    //Only a user that already has a database can replace an episode, the user ID is never used to make a new database file
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return Ok(resp),
+    };
+    match file_parser::replace_episode(&db_pool, season_num, episode_num, title, &file_name, contents, speaker_rules).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(err) if err.is::<file_parser::EncodingError>() => {
+            Ok(HttpResponse::BadRequest().json(json!({"error": format!("Transcript could not be decoded: {}", err)})))
+        }
         Err(err) => {
-            eprintln!("Error fetching transcript: {}", err);
-            HttpResponse::InternalServerError().body("Error fetching transcript")
+            eprintln!("Error replacing transcript: {}", err);
+            Ok(HttpResponse::InternalServerError().json(json!({"error": "Error replacing transcript"})))
+        }
+    }
+}
+
+///Endpoint to delete an episode and its lines, using the season + episode numbers
+#[delete("/transcripts/{season_num}/{episode_num}")]
+async fn delete_transcript(
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    path: web::Path<(i32, i32)>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let (season_num, episode_num) = path.into_inner();
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"}));
+    };
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match file_parser::delete_episode(&db_pool, season_num, episode_num).await {
+        Ok(Some(summary)) => HttpResponse::Ok().json(summary),
+        Ok(None) => HttpResponse::NotFound().json(json!({"error": format!("Season {} episode {} not found", season_num, episode_num)})),
+        Err(err) => {
+            eprintln!("Error deleting episode: {}", err);
+            HttpResponse::InternalServerError().json(json!({"error": "Error deleting episode"}))
+        }
+    }
+}
+
+///Endpoint to delete a season with all of its episodes and lines, using the season number
+#[delete("/transcripts/{season_num}")]
+async fn delete_season_transcripts(
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    season_num: web::Path<i32>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let season_num = season_num.into_inner();
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"}));
+    };
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match file_parser::delete_season(&db_pool, season_num).await {
+        Ok(Some(summary)) => HttpResponse::Ok().json(summary),
+        Ok(None) => HttpResponse::NotFound().json(json!({"error": format!("Season {} not found", season_num)})),
+        Err(err) => {
+            eprintln!("Error deleting season: {}", err);
+            HttpResponse::InternalServerError().json(json!({"error": "Error deleting season"}))
         }
     }
 }
@@ -479,10 +1029,7 @@ async fn get_speakers(
         Ok(pool) => pool,
         Err(resp) => return resp,
     };
-    let speakers = match sqlx::query_as::<_, Speaker>("SELECT * FROM speakers")
-        .fetch_all(&db_pool)
-        .await
-    {
+    let speakers = match speakers::list_speakers(&db_pool).await {
         Ok(data) => data,
         Err(err) => {
             eprintln!("Error fetching speakers: {}", err);
@@ -492,6 +1039,97 @@ async fn get_speakers(
     HttpResponse::Ok().json(speakers)
 }
 
+///Endpoint to rename a speaker, the old name becomes an alias of the speaker
+#[patch("/speakers/{speaker_id}")]
+async fn rename_speaker(
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    speaker_id: web::Path<i64>,
+    user_query: web::Query<UserQuery>,
+    body: web::Json<RenameSpeakerRequest>,
+) -> impl Responder {
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"}));
+    };
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match speakers::rename_speaker(&db_pool, speaker_id.into_inner(), &body.name).await {
+        Ok(speaker) => HttpResponse::Ok().json(speaker),
+        Err(err) => speaker_error_response(&err),
+    }
+}
+
+///Endpoint to merge several speakers into one, moving their lines over and keeping their names as aliases
+#[post("/speakers/merge")]
+async fn merge_speakers(
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    user_query: web::Query<UserQuery>,
+    body: web::Json<MergeSpeakersRequest>,
+) -> impl Responder {
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"}));
+    };
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match speakers::merge_speakers(&db_pool, body.into, &body.speakers).await {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(err) => speaker_error_response(&err),
+    }
+}
+
+///Endpoint to register an alias that later uploads resolve to the speaker
+#[post("/speakers/{speaker_id}/aliases")]
+async fn add_speaker_alias(
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    speaker_id: web::Path<i64>,
+    user_query: web::Query<UserQuery>,
+    body: web::Json<SpeakerAliasRequest>,
+) -> impl Responder {
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"}));
+    };
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match speakers::add_alias(&db_pool, speaker_id.into_inner(), &body.alias).await {
+        Ok(speaker) => HttpResponse::Ok().json(speaker),
+        Err(err) => speaker_error_response(&err),
+    }
+}
+
+///Endpoint to remove one of a speaker's aliases
+#[delete("/speakers/{speaker_id}/aliases/{alias}")]
+async fn remove_speaker_alias(
+    db_registry: web::Data<DatabaseRegistry>,
+    job_registry: web::Data<JobRegistry>,
+    edit_registry: web::Data<EditRegistry>,
+    path: web::Path<(i64, String)>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let (speaker_id, alias) = path.into_inner();
+    let Some(_edit) = reserve_edit(&job_registry, &edit_registry, &user_query.user_id).await else {
+        return HttpResponse::Conflict().json(json!({"error": "An upload is already being processed"}));
+    };
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match speakers::remove_alias(&db_pool, speaker_id, &alias).await {
+        Ok(speaker) => HttpResponse::Ok().json(speaker),
+        Err(err) => speaker_error_response(&err),
+    }
+}
+
 ///Endpoint to get all episodes in a season by the season's ID
 #[get("/seasons/{season_id}/episodes")]
 async fn get_episodes(
@@ -547,6 +1185,137 @@ async fn get_episode(
     }
 }
 
+///Endpoint to list an episode's scenes in order, each with the speakers that have lines in it
+#[get("/episodes/{episode_id}/scenes")]
+async fn get_scenes(
+    db_registry: web::Data<DatabaseRegistry>,
+    episode_id: web::Path<i64>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match sqlx::query_as::<_, Scene>(&format!("{} WHERE sc.episode_id = ? ORDER BY sc.number ASC", SCENE_SQL))
+        .bind(episode_id.into_inner())
+        .fetch_all(&db_pool)
+        .await
+    {
+        Ok(scenes) => HttpResponse::Ok().json(scenes),
+        Err(err) => {
+            eprintln!("Error fetching scenes: {}", err);
+            HttpResponse::InternalServerError().body("Error fetching scenes")
+        }
+    }
+}
+
+///Endpoint to get every line of a scene by the scene's ID
+#[get("/scenes/{scene_id}/lines")]
+async fn get_scene_lines(
+    db_registry: web::Data<DatabaseRegistry>,
+    scene_id: web::Path<i64>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    let scene = match sqlx::query_as::<_, Scene>(&format!("{} WHERE sc.id = ?", SCENE_SQL))
+        .bind(scene_id.into_inner())
+        .fetch_optional(&db_pool)
+        .await
+    {
+        Ok(Some(scene)) => scene,
+        Ok(None) => return HttpResponse::NotFound().body("Scene not found"),
+        Err(err) => {
+            eprintln!("Error fetching scene: {}", err);
+            return HttpResponse::InternalServerError().body("Error fetching scene");
+        }
+    };
+    match lines_between(&db_pool, scene.episode_id, scene.first_line_number, scene.last_line_number).await {
+        Ok(lines) => HttpResponse::Ok().json(lines),
+        Err(err) => {
+            eprintln!("Error fetching scene lines: {}", err);
+            HttpResponse::InternalServerError().body("Error fetching scene lines")
+        }
+    }
+}
+
+///Endpoint to check if a file is already loaded, using the SHA-256 hash of its contents, so a client can leave it out of an upload
+#[get("/files/{content_hash}")]
+async fn get_loaded_file(
+    db_registry: web::Data<DatabaseRegistry>,
+    content_hash: web::Path<String>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    let content_hash = content_hash.into_inner().trim().to_ascii_lowercase();
+    if content_hash.len() != 64 || !content_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return HttpResponse::BadRequest().json(json!({"error": "content_hash must be a SHA-256 hash in hex"}));
+    }
+    match sqlx::query_as::<_, Episode>("SELECT * FROM episodes WHERE content_hash = ? ORDER BY season_id, number")
+        .bind(&content_hash)
+        .fetch_all(&db_pool)
+        .await
+    {
+        Ok(episodes) => HttpResponse::Ok().json(LoadedFile { loaded: !episodes.is_empty(), content_hash, episodes }),
+        Err(err) => {
+            eprintln!("Error checking file: {}", err);
+            HttpResponse::InternalServerError().body("Error checking file")
+        }
+    }
+}
+
+///Endpoint to list the saved ingest reports, newest first
+#[get("/ingest-reports")]
+async fn get_ingest_reports(
+    db_registry: web::Data<DatabaseRegistry>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match sqlx::query_as::<_, IngestReportSummary>("SELECT id, created_at FROM ingest_reports ORDER BY id DESC")
+        .fetch_all(&db_pool)
+        .await
+    {
+        Ok(data) => HttpResponse::Ok().json(data),
+        Err(err) => {
+            eprintln!("Error fetching ingest reports: {}", err);
+            HttpResponse::InternalServerError().body("Error fetching ingest reports")
+        }
+    }
+}
+
+///Endpoint to get a saved ingest report by its ID
+#[get("/ingest-reports/{report_id}")]
+async fn get_ingest_report(
+    db_registry: web::Data<DatabaseRegistry>,
+    report_id: web::Path<i64>,
+    user_query: web::Query<UserQuery>,
+) -> impl Responder {
+    let db_pool = match get_db_pool(&db_registry, Some(&user_query.user_id)).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+    match sqlx::query_as::<_, StoredIngestReport>("SELECT id, created_at, report FROM ingest_reports WHERE id = ?")
+        .bind(report_id.into_inner())
+        .fetch_optional(&db_pool)
+        .await
+    {
+        Ok(Some(data)) => HttpResponse::Ok().json(data),
+        Ok(None) => HttpResponse::NotFound().body("Ingest report not found"),
+        Err(err) => {
+            eprintln!("Error fetching ingest report: {}", err);
+            HttpResponse::InternalServerError().body("Error fetching ingest report")
+        }
+    }
+}
+
 ///Defines /api scope and registers endpoints
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -554,12 +1323,29 @@ pub fn init_routes(cfg: &mut web::ServiceConfig) {
             .service(cleanup_db)
             .service(search_phrases)
             .service(get_random_line)
+            .service(get_transcript_by_absolute_number)
             .service(get_transcript)
+            .service(put_transcript)
+            .service(delete_transcript)
+            .service(delete_season_transcripts)
             .service(get_seasons)
             .service(get_speakers)
+            .service(merge_speakers)
+            .service(rename_speaker)
+            .service(add_speaker_alias)
+            .service(remove_speaker_alias)
             .service(get_episodes)
             .service(get_episode)
+            .service(get_scenes)
+            .service(get_scene_lines)
+            .service(get_loaded_file)
             .service(upload_zip)
+            .service(get_jobs)
+            .service(get_job)
+            .service(job_progress_ws)
+            .service(cancel_job)
+            .service(get_ingest_reports)
+            .service(get_ingest_report)
     );
 }
 
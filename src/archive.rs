@@ -0,0 +1,227 @@
+use crate::file_parser;
+use crate::models::SkippedFile;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+///Entries smaller than this are never rejected for their compression ratio, since tiny repetitive files compress very well
+const MIN_RATIO_CHECK_BYTES: u64 = 1024 * 1024;
+
+///Limits applied to uploads and the archives inside them, read from the environment with safe defaults
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    pub max_upload_bytes: u64,
+    pub max_uncompressed_bytes: u64,
+    pub max_entries: usize,
+    pub max_compression_ratio: u64,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        UploadLimits {
+            max_upload_bytes: 100 * 1024 * 1024,
+            max_uncompressed_bytes: 512 * 1024 * 1024,
+            max_entries: 10_000,
+            max_compression_ratio: 100,
+        }
+    }
+}
+
+impl UploadLimits {
+    ///Reads UPLOAD_MAX_BYTES, UPLOAD_MAX_UNCOMPRESSED_BYTES, UPLOAD_MAX_ENTRIES and UPLOAD_MAX_COMPRESSION_RATIO, keeping the default for any that are missing or invalid
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        }
+        let defaults = UploadLimits::default();
+        UploadLimits {
+            max_upload_bytes: var("UPLOAD_MAX_BYTES", defaults.max_upload_bytes),
+            max_uncompressed_bytes: var("UPLOAD_MAX_UNCOMPRESSED_BYTES", defaults.max_uncompressed_bytes),
+            max_entries: var("UPLOAD_MAX_ENTRIES", defaults.max_entries),
+            max_compression_ratio: var("UPLOAD_MAX_COMPRESSION_RATIO", defaults.max_compression_ratio),
+        }
+    }
+}
+
+///Reasons an upload or archive is rejected, each with its own error code for the API response
+#[derive(Debug)]
+pub enum ArchiveError {
+    UploadTooLarge { limit: u64 },
+    TooManyEntries { count: usize, limit: usize },
+    UncompressedTooLarge { limit: u64 },
+    CompressionRatioExceeded { entry: String, limit: u64 },
+    UnsafePath { entry: String },
+    Symlink { entry: String },
+    Invalid(String),
+    Io(io::Error),
+}
+
+impl ArchiveError {
+    ///Machine readable code for the error, returned alongside the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            ArchiveError::UploadTooLarge { .. } => "upload_too_large",
+            ArchiveError::TooManyEntries { .. } => "too_many_entries",
+            ArchiveError::UncompressedTooLarge { .. } => "uncompressed_too_large",
+            ArchiveError::CompressionRatioExceeded { .. } => "compression_ratio_exceeded",
+            ArchiveError::UnsafePath { .. } => "unsafe_path",
+            ArchiveError::Symlink { .. } => "symlink_entry",
+            ArchiveError::Invalid(_) => "invalid_zip",
+            ArchiveError::Io(_) => "io_error",
+        }
+    }
+
+    ///The archive entry that caused the error, if there is one
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            ArchiveError::CompressionRatioExceeded { entry, .. }
+            | ArchiveError::UnsafePath { entry }
+            | ArchiveError::Symlink { entry } => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::UploadTooLarge { limit } => write!(f, "Upload is larger than the {} byte limit", limit),
+            ArchiveError::TooManyEntries { count, limit } => {
+                write!(f, "ZIP file has {} entries, more than the limit of {}", count, limit)
+            }
+            ArchiveError::UncompressedTooLarge { limit } => {
+                write!(f, "ZIP file contents are larger than the {} byte limit", limit)
+            }
+            ArchiveError::CompressionRatioExceeded { entry, limit } => {
+                write!(f, "{} is compressed more than {} to 1", entry, limit)
+            }
+            ArchiveError::UnsafePath { entry } => write!(f, "{} has an absolute or parent directory path", entry),
+            ArchiveError::Symlink { entry } => write!(f, "{} is a symbolic link", entry),
+            ArchiveError::Invalid(message) => write!(f, "Invalid ZIP file: {}", message),
+            ArchiveError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+impl From<io::Error> for ArchiveError {
+    fn from(err: io::Error) -> Self {
+        ArchiveError::Io(err)
+    }
+}
+
+impl From<ZipError> for ArchiveError {
+    fn from(err: ZipError) -> Self {
+        match err {
+            ZipError::Io(err) => ArchiveError::Io(err),
+            err => ArchiveError::Invalid(err.to_string()),
+        }
+    }
+}
+
+///An uploaded file held in memory, either a zip archive whose entries are read on demand or a single transcript file.
+///Clones share the upload's bytes and its uncompressed size budget, so files can be read from several threads at once
+#[derive(Clone)]
+pub enum UploadSource {
+    Zip {
+        archive: ZipArchive<Cursor<Arc<[u8]>>>,
+        remaining_bytes: Arc<AtomicU64>,
+        limit: u64,
+    },
+    File {
+        name: String,
+        contents: Arc<[u8]>,
+    },
+}
+
+impl UploadSource {
+    ///Opens an uploaded zip file, checking every entry in its central directory against the limits before anything is read
+    pub fn zip(contents: Vec<u8>, limits: &UploadLimits) -> Result<Self, ArchiveError> {
+        let mut archive = ZipArchive::new(Cursor::new(Arc::from(contents)))?;
+        if archive.is_empty() {
+            return Err(ArchiveError::Invalid("ZIP file is empty".to_string()));
+        }
+        if archive.len() > limits.max_entries {
+            return Err(ArchiveError::TooManyEntries { count: archive.len(), limit: limits.max_entries });
+        }
+
+        let mut total_size: u64 = 0;
+        for i in 0..archive.len() {
+            let zip_file = archive.by_index_raw(i)?;
+            let entry = zip_file.name().to_string();
+            if zip_file.enclosed_name().is_none() {
+                return Err(ArchiveError::UnsafePath { entry });
+            }
+            if zip_file.is_symlink() {
+                return Err(ArchiveError::Symlink { entry });
+            }
+            total_size = total_size.saturating_add(zip_file.size());
+            if total_size > limits.max_uncompressed_bytes {
+                return Err(ArchiveError::UncompressedTooLarge { limit: limits.max_uncompressed_bytes });
+            }
+            let exceeds_ratio = zip_file.size() >= MIN_RATIO_CHECK_BYTES
+                && zip_file.size() / zip_file.compressed_size().max(1) > limits.max_compression_ratio;
+            if exceeds_ratio {
+                return Err(ArchiveError::CompressionRatioExceeded { entry, limit: limits.max_compression_ratio });
+            }
+        }
+        Ok(UploadSource::Zip {
+            archive,
+            remaining_bytes: Arc::new(AtomicU64::new(limits.max_uncompressed_bytes)),
+            limit: limits.max_uncompressed_bytes,
+        })
+    }
+
+    ///Wraps a single uploaded transcript file
+    pub fn file(name: &str, contents: Vec<u8>) -> Self {
+        UploadSource::File { name: name.to_string(), contents: Arc::from(contents) }
+    }
+
+    ///Lists the transcript files and manifest in the upload, and the files that will be left out
+    pub fn entries(&self) -> (Vec<String>, Vec<SkippedFile>) {
+        match self {
+            UploadSource::Zip { archive, .. } => {
+                let mut entries = Vec::new();
+                let mut skipped = Vec::new();
+                for name in archive.file_names() {
+                    if name.ends_with('/') {
+                        continue;
+                    }
+                    if file_parser::is_supported_file(name) || file_parser::is_manifest(name) {
+                        entries.push(name.to_string());
+                    } else {
+                        skipped.push(SkippedFile {
+                            file: name.to_string(),
+                            reason: "Unsupported file type".to_string(),
+                        });
+                    }
+                }
+                (entries, skipped)
+            }
+            UploadSource::File { name, .. } => (vec![name.clone()], Vec::new()),
+        }
+    }
+
+    ///Reads a file from the upload.
+    ///The uncompressed size limit is enforced on the bytes actually read, since the sizes in the central directory can't be trusted
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, ArchiveError> {
+        match self {
+            UploadSource::Zip { archive, remaining_bytes, limit } => {
+                let zip_file = archive.by_name(name)?;
+                let mut contents = Vec::new();
+                zip_file.take(remaining_bytes.load(Ordering::SeqCst).saturating_add(1)).read_to_end(&mut contents)?;
+                remaining_bytes
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| remaining.checked_sub(contents.len() as u64))
+                    .map_err(|_| ArchiveError::UncompressedTooLarge { limit: *limit })?;
+                Ok(contents)
+            }
+            UploadSource::File { name: file_name, contents } if file_name == name => Ok(contents.to_vec()),
+            UploadSource::File { .. } => Err(ArchiveError::Io(io::Error::new(io::ErrorKind::NotFound, name.to_string()))),
+        }
+    }
+}
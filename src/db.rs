@@ -16,11 +16,19 @@ pub async fn remove_cache(user_id: &str) {
     cache.remove(user_id);
 }
 
+///Checks if a user ID is safe to use in a database file name, only letters, digits, "_" and "-"
+pub fn is_valid_user_id(user_id: &str) -> bool {
+    !user_id.is_empty() && user_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 ///Makes a database for a user and applies schema, and returns the connection pool and database file path.
 pub async fn setup_database(
     user_id: &str,
     schema_path: &str,
 ) -> Result<(SqlitePool, PathBuf), Box<dyn std::error::Error>> {
+    if !is_valid_user_id(user_id) {
+        return Err(format!("Invalid user ID: {}", user_id).into());
+    }
     let db_path = Path::new("./temp_dbs").join(format!("{}.sqlite", user_id));
     println!("Setting up database at {:?}", db_path);
 
@@ -67,3 +75,18 @@ pub async fn setup_database(
     cache.insert(user_id.to_string(), db_pool.clone());
     Ok((db_pool, db_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_valid_user_id_rejects_paths() {
+        assert!(is_valid_user_id("default"));
+        assert!(is_valid_user_id("user_1-a"));
+        assert!(!is_valid_user_id(""));
+        assert!(!is_valid_user_id("../../x"));
+        assert!(!is_valid_user_id("/tmp/x"));
+        assert!(!is_valid_user_id("a.b"));
+    }
+}
@@ -1,165 +1,1506 @@
+mod ass;
+mod delimited;
+mod fountain;
+mod header;
+mod kinds;
+mod manifest;
+mod scenes;
+mod speakers;
+mod srt;
+mod structured;
+mod vtt;
+
 use std::path::Path;
-use tokio::{
-    fs::File,
-    io::{self as tokio_io, AsyncBufReadExt, AsyncWriteExt, BufReader},
-};
 use regex::Regex;
-use walkdir::WalkDir;
-use sqlx::SqlitePool;
-use std::collections::HashMap;
-
-///Parses a given filename to get the season + episode numbers, and episode title using regex
-fn parse_episode_filename(filename: &str, parent_dir: Option<&str>) -> Option<(i32, i32, String)> {
-    let filename = filename.trim();
-    let season_episode_regex = Regex::new(r"(?i)^(\d{1,2})x(\d{1,2})\s*-\s*(.+)\.txt$").ok()?;
-    let sxxe_regex = Regex::new(r"(?i)^s(\d{1,2})e(\d{1,2})(?:\s*-\s*(.+))?\.txt$").ok()?;
-    let e_regex = Regex::new(r"(?i)^e(\d+)\s*-\s*(.+)\.txt$").ok()?;    
-    let season_dir_regex = Regex::new(r"(?i)(?:season)?\s*S?(\d{1,2})").ok()?;
-
-    if let Some(caps) = season_episode_regex.captures(filename) {
-        let season_num = caps[1].parse().ok()?;
-        let episode_num = caps[2].parse().ok()?;
-        let title = caps[3].trim().to_string();
-        Some((season_num, episode_num, title))
-    } else if let Some(caps) = sxxe_regex.captures(filename) {
-        let season_num = caps[1].parse().ok()?;
-        let episode_num = caps[2].parse().ok()?;
-        let title = caps.get(3).map_or("", |m| m.as_str()).trim().to_string();
-        Some((season_num, episode_num, title))
-    } else if let Some(caps) = e_regex.captures(filename) {
-        let season_num = parent_dir.and_then(|parent| {
-            season_dir_regex
-                .captures(parent)?
-                .get(1)?
-                .as_str()
-                .parse::<i32>()
-                .ok()
-        })?;
-        let episode_num = caps[1].parse().ok()?;
-        let title = caps[2].trim().to_string();
-        Some((season_num, episode_num, title))
+use lazy_static::lazy_static;
+use sqlx::{Connection, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use crate::archive::{ArchiveError, UploadSource};
+use crate::jobs::Job;
+use crate::models::{
+    CsvColumnMapping, DeleteSummary, Episode, EpisodeChange, EpisodeCollision, EpisodeReport, IngestReport, IngestThroughput, IngestWarning, LineKind,
+    ProgressEvent, SkippedFile, UploadMode,
+};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use header::{EpisodeDetails, Header};
+
+pub use speakers::SpeakerDetector;
+
+///File extensions that can be parsed into transcripts
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["txt", "srt", "vtt", "ass", "ssa", "fountain", "json", "jsonl", "csv", "tsv"];
+
+///Lines inserted per INSERT statement, 500 rows of 13 columns stays well under SQLite's bound parameter limit
+const LINE_BATCH_SIZE: usize = 500;
+
+///Pragmas used while loading an upload, a bigger page cache and temporary tables kept in memory
+const BULK_LOAD_PRAGMAS: &[(&str, i64)] = &[("cache_size", -65536), ("temp_store", 2)];
+
+///synchronous setting for a replace upload into a dataset with nothing loaded yet. Durability is turned off since there is no data to lose if the load is cut short
+const FRESH_LOAD_SYNCHRONOUS: i64 = 0;
+
+///synchronous setting for every other upload, which writes into a dataset that has to survive a crash
+const SAFE_LOAD_SYNCHRONOUS: i64 = 1;
+
+lazy_static! {
+    static ref CUE_TAG_REGEX: Regex = Regex::new(r"<[^>]*>|\{\\[^}]*\}").unwrap();
+    //3x01 - Title, 3x01-02 - Title or 3x01-3x02 - Title
+    static ref SEASON_X_EPISODE_REGEX: Regex =
+        Regex::new(r"(?i)^(\d{1,2})x(\d{1,4})(?:-(?:\d{1,2}x)?(\d{1,4}))?\s*-\s*(.+)$").unwrap();
+    //S01E01, S01E01E02, S01E01-E02 or S01E01-02, with an optional " - Title"
+    static ref SXXE_REGEX: Regex =
+        Regex::new(r"(?i)^s(\d{1,2})e(\d{1,4})(?:-?e(\d{1,4})|-(\d{1,4}))?(?:\s*-\s*(.+))?$").unwrap();
+    //E01, Ep 01 or Episode 123, with an optional range and " - Title"
+    static ref EPISODE_REGEX: Regex =
+        Regex::new(r"(?i)^(?:e|ep|episode)\s*(\d{1,4})(?:-(?:e|ep)?(\d{1,4}))?(?:\s*-\s*(.+))?$").unwrap();
+    //Special - Title or Special 2 - Title
+    static ref SPECIAL_REGEX: Regex = Regex::new(r"(?i)^special\s*(\d{1,4})?\s*-\s*(.+)$").unwrap();
+    static ref SEASON_DIR_REGEX: Regex = Regex::new(r"(?i)(?:season)?\s*S?(\d{1,2})").unwrap();
+    static ref SPECIALS_DIR_REGEX: Regex = Regex::new(r"(?i)^specials?$").unwrap();
+    static ref DATE_REGEX: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+}
+
+///A single parsed transcript line before it is inserted into the database
+#[derive(Clone, Default)]
+struct ParsedLine {
+    speaker: Option<String>,
+    speakers: Vec<String>,
+    speaker_label: Option<String>,
+    content: String,
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    cue_settings: Option<String>,
+    style: Option<String>,
+    parenthetical: Option<String>,
+    scene_heading: Option<String>,
+    line_number: Option<i32>,
+    kind: LineKind,
+}
+
+///Per-upload settings for how files are ingested
+#[derive(Default)]
+pub struct IngestOptions {
+    pub csv_mapping: CsvColumnMapping,
+    pub mode: UploadMode,
+    pub speaker_rules: Arc<SpeakerDetector>,
+    ///Loads files the episode was already loaded from again instead of skipping them, for example after changing the speaker rules
+    pub reprocess: bool,
+}
+
+///Row-level errors found while validating structured and CSV/TSV transcript files
+#[derive(Debug)]
+pub struct ValidationErrors(pub Vec<String>);
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid transcript data: {}", self.0.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+///A file whose character encoding couldn't be detected or that doesn't decode as text
+#[derive(Debug)]
+pub struct EncodingError(pub String);
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+///An episode's parsed lines, the header block from the top of its file, and the character encoding its file was decoded from
+struct ParsedFile {
+    lines: Vec<ParsedLine>,
+    header: Header,
+    encoding: &'static str,
+}
+
+///Where an episode's lines come from, either a transcript file in the upload still to be parsed or an already parsed structured file
+enum EpisodeSource {
+    File(String),
+    Parsed(ParsedFile),
+}
+
+///An episode's lines, either still being read, decoded and parsed on the blocking thread pool or already parsed
+enum ParseTask {
+    Parsing(JoinHandle<Result<ReadFile, ArchiveError>>),
+    Parsed(ParsedFile),
+}
+
+///A file read from the upload on the blocking thread pool, with its hash and what was parsed from it, or None if it is unchanged
+struct ReadFile<T = ParsedFile> {
+    content_hash: String,
+    parsed: Option<Result<T, EncodingError>>,
+    parse_time: Duration,
+}
+
+///The episodes parsed from a structured or CSV/TSV file, or the file's validation errors
+struct StructuredFile {
+    episodes: Result<Vec<structured::StructuredEpisode>, Vec<String>>,
+    encoding: &'static str,
+}
+
+///An episode found in the upload, waiting to be inserted into the database
+struct PendingEpisode {
+    file: String,
+    number: i32,
+    title: String,
+    details: EpisodeDetails,
+    metadata: Option<String>,
+    number_end: Option<i32>,
+    absolute_number: Option<i32>,
+    ///Hash of the file the episode comes from, set once the file is read
+    content_hash: Option<String>,
+    ///Text encoding of the file the episode comes from, set once the file is decoded
+    encoding: Option<&'static str>,
+    source: EpisodeSource,
+}
+
+///The episode details parsed from a transcript's filename
+struct EpisodeFilename {
+    season: i32,
+    ///None for a special without a number, which is given the next free number in season 0
+    episode: Option<i32>,
+    ///The last episode of a multi-part file like 3x01-02
+    episode_end: Option<i32>,
+    ///Set for files numbered across the whole show, like E123 outside of a season folder
+    absolute_number: Option<i32>,
+    title: String,
+}
+
+///Checks if a path inside an upload is a manifest at the archive root
+pub fn is_manifest(path: &str) -> bool {
+    manifest::MANIFEST_FILES.contains(&manifest::normalize_path(path).as_str())
+}
+
+///Checks if a value is a date formatted as YYYY-MM-DD
+pub fn is_date(value: &str) -> bool {
+    DATE_REGEX.is_match(value)
+}
+
+///Hashes a file's contents with SHA-256, as lowercase hex
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+///Gets the content hash of every episode's file, keyed by season and episode number
+pub async fn content_hashes<'c, E: sqlx::Executor<'c, Database = Sqlite>>(executor: E) -> Result<HashMap<(i32, i32), String>, sqlx::Error> {
+    let rows: Vec<(i32, i32, String)> = sqlx::query_as(
+        "SELECT s.number, e.number, e.content_hash FROM episodes e JOIN seasons s ON e.season_id = s.id WHERE e.content_hash IS NOT NULL",
+    )
+    .fetch_all(executor)
+    .await?;
+    Ok(rows.into_iter().map(|(season, episode, hash)| ((season, episode), hash)).collect())
+}
+
+///Compares a file's hash to the one its episode was last loaded from
+fn episode_change(loaded_hash: Option<&str>, content_hash: &str) -> EpisodeChange {
+    match loaded_hash {
+        None => EpisodeChange::New,
+        Some(loaded_hash) if loaded_hash == content_hash => EpisodeChange::Unchanged,
+        Some(_) => EpisodeChange::Changed,
+    }
+}
+
+///Checks if a file name has one of the supported transcript extensions
+pub fn is_supported_file(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.iter().any(|supported| ext.eq_ignore_ascii_case(supported)))
+}
+
+///Gets the lowercased extension of a structured or CSV/TSV transcript file
+fn structured_extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .filter(|ext| ["json", "jsonl", "csv", "tsv"].contains(&ext.as_str()))
+}
+
+///Splits a "Speaker: content" line into the speaker and the content, if the text before the colon passes the speaker rules
+fn split_speaker(line: &str, speakers: &SpeakerDetector) -> (Option<String>, String) {
+    match line.split_once(':') {
+        Some((speaker, content)) if speakers.is_speaker(speaker.trim(), content.trim()) => {
+            (Some(speaker.trim().to_string()), content.trim().to_string())
+        }
+        _ => (None, line.trim().to_string()),
+    }
+}
+
+///Parses a plain text transcript where each line is a "Speaker: content" pair or an untagged line
+fn parse_plain_text(text: &str, speakers: &SpeakerDetector) -> Vec<ParsedLine> {
+    text.lines()
+        .map(|line| {
+            let (speaker, content) = split_speaker(line, speakers);
+            ParsedLine { speaker, content, ..Default::default() }
+        })
+        .collect()
+}
+
+///Splits subtitle text into blocks of non-blank lines separated by blank lines
+fn split_blocks(text: &str) -> Vec<Vec<&str>> {
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+///Converts a subtitle timecode ([HH:]MM:SS,mmm, [HH:]MM:SS.mmm or H:MM:SS.cc) into milliseconds
+fn parse_timecode(timecode: &str) -> Option<i64> {
+    //Only plain digits are accepted, since parse() would also take a sign like "-1" or "+1"
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit()).then(|| part.parse::<i64>().ok()).flatten();
+    let (clock, fraction) = timecode.trim().split_once([',', '.'])?;
+    let parts: Vec<i64> = clock.split(':').map(digits).collect::<Option<_>>()?;
+    let (hours, minutes, seconds) = match parts[..] {
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        [minutes, seconds] => (0, minutes, seconds),
+        _ => return None,
+    };
+    if fraction.len() > 3 {
+        return None;
+    }
+    let millis = digits(fraction)? * 10_i64.pow(3 - fraction.len() as u32);
+    //A timecode too big to fit in milliseconds is malformed rather than wrapping around
+    hours
+        .checked_mul(60)?
+        .checked_add(minutes)?
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(millis)
+}
+
+///Turns the text lines of a subtitle cue into parsed lines, starting a new line whenever a text line has its own speaker
+fn parse_cue_text(text_lines: &[&str], template: &ParsedLine, speakers: &SpeakerDetector) -> Vec<ParsedLine> {
+    let mut cue: Vec<ParsedLine> = Vec::new();
+    for text_line in text_lines {
+        let text_line = CUE_TAG_REGEX.replace_all(text_line, "");
+        let text_line = text_line.trim();
+        if text_line.is_empty() {
+            continue;
+        }
+        match (split_speaker(text_line, speakers), cue.last_mut()) {
+            ((None, content), Some(current)) => {
+                current.content.push(' ');
+                current.content.push_str(&content);
+            }
+            ((speaker, content), _) => cue.push(ParsedLine {
+                speaker,
+                content,
+                ..template.clone()
+            }),
+        }
+    }
+    cue
+}
+
+///Splits off the file's header block, picks the parser for the rest based on the extension, then normalizes the speaker names it finds and classifies the lines
+fn parse_transcript(path: &Path, text: &str, speakers: &SpeakerDetector) -> (Header, Vec<ParsedLine>) {
+    let (header, text) = header::split_header(text);
+    let mut lines = match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase()).as_deref() {
+        Some("srt") => srt::parse(text, speakers),
+        Some("vtt") => vtt::parse(text, speakers),
+        Some("ass" | "ssa") => ass::parse(text),
+        Some("fountain") => fountain::parse(text),
+        _ if fountain::looks_like_screenplay(text, speakers) => fountain::parse(text),
+        _ => parse_plain_text(text, speakers),
+    };
+    speakers.apply(&mut lines);
+    kinds::classify(&mut lines);
+    (header, lines)
+}
+
+///Parses a given filename to get the season + episode numbers, and episode title using regex.
+///Files in a "Specials" folder or named "Special - Title" go in season 0, and E123 files outside of a season folder are numbered across the whole show
+fn parse_episode_filename(filename: &str, parent_dir: Option<&str>) -> Option<EpisodeFilename> {
+    let filename = Path::new(filename.trim()).file_stem()?.to_str()?.trim();
+    let title = |caps: &regex::Captures, group: usize| caps.get(group).map_or("", |m| m.as_str()).trim().to_string();
+    //Ranges that end before they start are read as a single episode
+    let range_end = |start: i32, end: Option<&str>| end.and_then(|end| end.parse().ok()).filter(|end| *end > start);
+    let dir_season = parent_dir.and_then(|parent| {
+        if SPECIALS_DIR_REGEX.is_match(parent.trim()) {
+            return Some(0);
+        }
+        SEASON_DIR_REGEX.captures(parent)?.get(1)?.as_str().parse::<i32>().ok()
+    });
+
+    if let Some(caps) = SEASON_X_EPISODE_REGEX.captures(filename) {
+        let episode = caps[2].parse().ok()?;
+        Some(EpisodeFilename {
+            season: caps[1].parse().ok()?,
+            episode: Some(episode),
+            episode_end: range_end(episode, caps.get(3).map(|m| m.as_str())),
+            absolute_number: None,
+            title: title(&caps, 4),
+        })
+    } else if let Some(caps) = SXXE_REGEX.captures(filename) {
+        let episode = caps[2].parse().ok()?;
+        Some(EpisodeFilename {
+            season: caps[1].parse().ok()?,
+            episode: Some(episode),
+            episode_end: range_end(episode, caps.get(3).or(caps.get(4)).map(|m| m.as_str())),
+            absolute_number: None,
+            title: title(&caps, 5),
+        })
+    } else if let Some(caps) = EPISODE_REGEX.captures(filename) {
+        let episode = caps[1].parse().ok()?;
+        let episode_end = range_end(episode, caps.get(2).map(|m| m.as_str()));
+        match dir_season {
+            Some(season) => Some(EpisodeFilename { season, episode: Some(episode), episode_end, absolute_number: None, title: title(&caps, 3) }),
+            //Without a season folder the number counts across the whole show, and the episode goes in season 1
+            None => Some(EpisodeFilename {
+                season: 1,
+                episode: Some(episode),
+                episode_end,
+                absolute_number: Some(episode),
+                title: title(&caps, 3),
+            }),
+        }
+    } else if let Some(caps) = SPECIAL_REGEX.captures(filename) {
+        Some(EpisodeFilename {
+            season: 0,
+            episode: caps.get(1).and_then(|m| m.as_str().parse().ok()),
+            episode_end: None,
+            absolute_number: None,
+            title: title(&caps, 2),
+        })
+    } else if dir_season == Some(0) {
+        Some(EpisodeFilename { season: 0, episode: None, episode_end: None, absolute_number: None, title: filename.to_string() })
     } else {
         None
     }
 }
 
-///Iterates through the directory and gets all text files, sorts them, uses regex to get episode data, then inserts the speakers + lines into the database
-pub async fn process_seasons(
-    pool: &SqlitePool,
-    extract_dir: &Path,
-    _user_id: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if !extract_dir.exists() {
-        return Err(Box::from(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Directory not found: {}", extract_dir.display()),
-        )));
+///Adds a warning to the report and sends it to anyone following the job's progress
+fn add_warning(report: &mut IngestReport, job: &Job, file: Option<String>, message: String) {
+    job.emit(ProgressEvent::Warning { file: file.clone(), message: message.clone() });
+    report.warnings.push(IngestWarning { file, message });
+}
+
+///Adds a season into the database, or gets the existing one's ID
+async fn upsert_season(conn: &mut SqliteConnection, season_num: i32) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO seasons (number) VALUES (?) ON CONFLICT(number) DO UPDATE SET number = excluded.number RETURNING id",
+    )
+    .bind(season_num)
+    .fetch_one(conn)
+    .await
+}
+
+///Stores a list of names as a JSON array, or NULL if it is empty
+fn json_list(names: &[String]) -> Option<String> {
+    (!names.is_empty()).then(|| serde_json::to_string(names).unwrap_or_default())
+}
+
+///Adds an episode associated with a season into the database, updating its details if it already exists.
+///An existing episode keeps its air date, writers, directors, synopsis, production code and metadata when the new details don't have them
+async fn upsert_episode(conn: &mut SqliteConnection, season_id: i64, episode: &PendingEpisode) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO episodes (season_id, number, title, air_date, writers, directors, synopsis, production_code, metadata, number_end, absolute_number, content_hash, encoding)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(season_id, number) DO UPDATE SET
+            title = excluded.title,
+            air_date = COALESCE(excluded.air_date, episodes.air_date),
+            writers = COALESCE(excluded.writers, episodes.writers),
+            directors = COALESCE(excluded.directors, episodes.directors),
+            synopsis = COALESCE(excluded.synopsis, episodes.synopsis),
+            production_code = COALESCE(excluded.production_code, episodes.production_code),
+            metadata = COALESCE(excluded.metadata, episodes.metadata),
+            number_end = excluded.number_end,
+            absolute_number = COALESCE(excluded.absolute_number, episodes.absolute_number),
+            content_hash = excluded.content_hash,
+            encoding = excluded.encoding
+        RETURNING id
+        "#,
+    )
+    .bind(season_id)
+    .bind(episode.number)
+    .bind(&episode.title)
+    .bind(&episode.details.air_date)
+    .bind(json_list(&episode.details.writers))
+    .bind(json_list(&episode.details.directors))
+    .bind(&episode.details.synopsis)
+    .bind(&episode.details.production_code)
+    .bind(&episode.metadata)
+    .bind(episode.number_end)
+    .bind(episode.absolute_number)
+    .bind(&episode.content_hash)
+    .bind(episode.encoding)
+    .fetch_one(conn)
+    .await
+}
+
+///Numbers the specials that have no number in their filename, each gets the number of a special already loaded with the same title, or else the next free number in season 0.
+///Reusing the number means loading a special again replaces it rather than adding a copy.
+///When `keep_loaded` is false the loaded specials without a matching title are about to be deleted, so their numbers are free again
+async fn number_specials(
+    conn: &mut SqliteConnection,
+    season_episodes: &mut HashMap<i32, Vec<PendingEpisode>>,
+    mut unnumbered: Vec<PendingEpisode>,
+    keep_loaded: bool,
+) -> Result<(), sqlx::Error> {
+    if unnumbered.is_empty() {
+        return Ok(());
     }
+    unnumbered.sort_by(|a, b| a.file.cmp(&b.file));
+    let loaded: Vec<(i32, Option<i32>, String)> = sqlx::query_as(
+        "SELECT e.number, e.number_end, e.title FROM episodes e JOIN seasons s ON e.season_id = s.id WHERE s.number = 0",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
 
-    let mut transaction = pool.begin().await?;
-    let mut entries: Vec<_> = WalkDir::new(extract_dir)
+    let specials = season_episodes.entry(0).or_default();
+    let mut unmatched = Vec::new();
+    for mut episode in unnumbered {
+        match loaded.iter().find(|(_, _, title)| !title.is_empty() && *title == episode.title) {
+            Some((number, _, _)) => {
+                episode.number = *number;
+                specials.push(episode);
+            }
+            None => unmatched.push(episode),
+        }
+    }
+    let next = loaded
+        .iter()
+        .filter(|_| keep_loaded)
+        .map(|(number, number_end, _)| number_end.unwrap_or(*number))
+        .chain(specials.iter().map(|episode| episode.number_end.unwrap_or(episode.number)))
+        .max()
+        .map_or(1, |last| last + 1);
+    for (number, mut episode) in (next..).zip(unmatched) {
+        episode.number = number;
+        specials.push(episode);
+    }
+    Ok(())
+}
+
+///Gets the ID of an episode that is already in the database
+async fn find_episode(conn: &mut SqliteConnection, season_id: i64, episode_num: i32) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM episodes WHERE season_id = ? AND number = ?")
+        .bind(season_id)
+        .bind(episode_num)
+        .fetch_optional(conn)
+        .await
+}
+
+///Deletes an episode's lines and scenes before it is loaded again.
+///The lines are taken out of the FTS index in one statement first, since a load drops the delete trigger while it runs
+async fn delete_episode_lines(conn: &mut SqliteConnection, episode_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM lines_fts WHERE rowid IN (SELECT id FROM lines WHERE episode_id = ?)")
+        .bind(episode_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM lines WHERE episode_id = ?")
+        .bind(episode_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM scenes WHERE episode_id = ?")
+        .bind(episode_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+///Deletes an episode with its lines and scenes, so it can be loaded again without keeping any of its old details
+async fn delete_episode_row(conn: &mut SqliteConnection, episode_id: i64) -> Result<(), sqlx::Error> {
+    delete_episode_lines(conn, episode_id).await?;
+    sqlx::query("DELETE FROM episodes WHERE id = ?").bind(episode_id).execute(&mut *conn).await?;
+    Ok(())
+}
+
+///Deletes every episode a replace upload didn't load or keep, keyed by season and episode number, then the seasons left without episodes.
+///Episodes are deleted a batch at a time with their lines' FTS rows, as the load drops the delete trigger rather than fire it for every line.
+///Speaker aliases and ingest reports are kept, so aliases keep applying to later uploads
+async fn delete_episodes_except(conn: &mut SqliteConnection, keep: &HashSet<(i32, i32)>) -> Result<(), sqlx::Error> {
+    let episodes: Vec<(i64, i32, i32)> =
+        sqlx::query_as("SELECT e.id, s.number, e.number FROM episodes e JOIN seasons s ON e.season_id = s.id")
+            .fetch_all(&mut *conn)
+            .await?;
+    let deleted: Vec<i64> = episodes
         .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file() && e.path().extension().map_or(false, |ext| ext == "txt"))
+        .filter(|(_, season_num, episode_num)| !keep.contains(&(*season_num, *episode_num)))
+        .map(|(episode_id, _, _)| episode_id)
         .collect();
+    for batch in deleted.chunks(LINE_BATCH_SIZE) {
+        let episode_ids = batch.iter().map(i64::to_string).collect::<Vec<_>>().join(", ");
+        for statement in [
+            format!("DELETE FROM lines_fts WHERE rowid IN (SELECT id FROM lines WHERE episode_id IN ({}))", episode_ids),
+            format!("DELETE FROM lines WHERE episode_id IN ({})", episode_ids),
+            format!("DELETE FROM scenes WHERE episode_id IN ({})", episode_ids),
+            format!("DELETE FROM episodes WHERE id IN ({})", episode_ids),
+        ] {
+            sqlx::query(&statement).execute(&mut *conn).await?;
+        }
+    }
+    sqlx::query("DELETE FROM seasons WHERE id NOT IN (SELECT season_id FROM episodes)")
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+///Inserts the scenes found in an episode's lines
+async fn insert_scenes(conn: &mut SqliteConnection, episode_id: i64, scenes: &[scenes::ParsedScene]) -> Result<(), sqlx::Error> {
+    for (batch_index, batch) in scenes.chunks(LINE_BATCH_SIZE).enumerate() {
+        let first_scene_number = (batch_index * LINE_BATCH_SIZE) as i32 + 1;
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO scenes (episode_id, number, heading, location, first_line_number, last_line_number) ",
+        );
+        query.push_values((first_scene_number..).zip(batch), |mut row, (number, scene)| {
+            row.push_bind(episode_id)
+                .push_bind(number)
+                .push_bind(&scene.heading)
+                .push_bind(&scene.location)
+                .push_bind(scene.first_line_number)
+                .push_bind(scene.last_line_number);
+        });
+        query.build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+///Deletes speakers that no longer have any lines, after lines are replaced or deleted
+async fn remove_orphan_speakers(conn: &mut SqliteConnection) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM speakers WHERE id NOT IN (SELECT speaker_id FROM line_speakers)",
+    )
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+///Adds a speaker into the database, or gets the existing one's ID
+async fn upsert_speaker(conn: &mut SqliteConnection, name: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO speakers (name) VALUES (?) ON CONFLICT(name) DO UPDATE SET name = excluded.name RETURNING id",
+    )
+    .bind(name)
+    .fetch_one(conn)
+    .await
+}
+
+///Speaker IDs by name and speaker names by lowercased alias, so each speaker is only looked up once per upload rather than once per line
+struct SpeakerCache {
+    ids: HashMap<String, i64>,
+    aliases: HashMap<String, String>,
+}
+
+impl SpeakerCache {
+    ///Loads the speakers and aliases already in the database
+    async fn load(conn: &mut SqliteConnection) -> Result<Self, sqlx::Error> {
+        let ids = sqlx::query_as::<_, (String, i64)>("SELECT name, id FROM speakers")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+        let aliases = sqlx::query_as::<_, (String, String)>("SELECT alias, name FROM speaker_aliases")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|(alias, name)| (alias.to_lowercase(), name))
+            .collect();
+        Ok(SpeakerCache { ids, aliases })
+    }
+
+    ///Gets a speaker's ID, resolving a registered alias to its speaker and only going to the database the first time a speaker is seen
+    async fn id(&mut self, conn: &mut SqliteConnection, name: &str) -> Result<i64, sqlx::Error> {
+        let name = self.aliases.get(&name.to_lowercase()).map_or(name, String::as_str);
+        if let Some(id) = self.ids.get(name) {
+            return Ok(*id);
+        }
+        let id = upsert_speaker(conn, name).await?;
+        self.ids.insert(name.to_string(), id);
+        Ok(id)
+    }
+}
+
+///Inserts an episode's lines and their speakers, using one multi-row INSERT per batch of lines.
+///Each line's first speaker is also kept in lines.speaker_id, and every speaker of the line goes into line_speakers
+async fn insert_lines(
+    conn: &mut SqliteConnection,
+    speakers: &mut SpeakerCache,
+    season_id: i64,
+    episode_id: i64,
+    lines: &[ParsedLine],
+) -> Result<(), sqlx::Error> {
+    for (batch_index, batch) in lines.chunks(LINE_BATCH_SIZE).enumerate() {
+        let mut line_speaker_ids = Vec::with_capacity(batch.len());
+        for parsed in batch {
+            let mut speaker_ids = Vec::with_capacity(parsed.speakers.len());
+            for speaker in &parsed.speakers {
+                //Two names on a line can be aliases of the same speaker
+                let speaker_id = speakers.id(conn, speaker).await?;
+                if !speaker_ids.contains(&speaker_id) {
+                    speaker_ids.push(speaker_id);
+                }
+            }
+            line_speaker_ids.push(speaker_ids);
+        }
+
+        let first_line_number = (batch_index * LINE_BATCH_SIZE) as i32 + 1;
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO lines (season_id, episode_id, speaker_id, speaker_label, line_number, content, start_ms, end_ms, cue_settings, style, parenthetical, scene_heading, kind) ",
+        );
+        query.push_values((first_line_number..).zip(batch.iter().zip(&line_speaker_ids)), |mut row, (line_num, (parsed, speaker_ids))| {
+            row.push_bind(season_id)
+                .push_bind(episode_id)
+                .push_bind(speaker_ids.first())
+                .push_bind(&parsed.speaker_label)
+                .push_bind(parsed.line_number.unwrap_or(line_num))
+                .push_bind(&parsed.content)
+                .push_bind(parsed.start_ms)
+                .push_bind(parsed.end_ms)
+                .push_bind(&parsed.cue_settings)
+                .push_bind(&parsed.style)
+                .push_bind(&parsed.parenthetical)
+                .push_bind(&parsed.scene_heading)
+                .push_bind(parsed.kind);
+        });
+        let result = query.build().execute(&mut *conn).await?;
+
+        //The rows of one INSERT get consecutive IDs, so the batch's line IDs are worked out from the last one
+        let first_line_id = result.last_insert_rowid() - batch.len() as i64 + 1;
+        let line_speakers: Vec<(i64, usize, i64)> = (first_line_id..)
+            .zip(&line_speaker_ids)
+            .flat_map(|(line_id, speaker_ids)| {
+                speaker_ids.iter().enumerate().map(move |(position, speaker_id)| (line_id, position, *speaker_id))
+            })
+            .collect();
+        for chunk in line_speakers.chunks(LINE_BATCH_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new("INSERT INTO line_speakers (line_id, position, speaker_id) ");
+            query.push_values(chunk, |mut row, (line_id, position, speaker_id)| {
+                row.push_bind(*line_id).push_bind(*position as i64).push_bind(*speaker_id);
+            });
+            query.build().execute(&mut *conn).await?;
+        }
+    }
+    Ok(())
+}
+
+///Sets each pragma on the connection, returning the values they had before so they can be put back
+async fn set_pragmas(
+    conn: &mut SqliteConnection,
+    pragmas: &[(&'static str, i64)],
+) -> Result<Vec<(&'static str, i64)>, sqlx::Error> {
+    let mut previous = Vec::with_capacity(pragmas.len());
+    for (name, value) in pragmas {
+        let old_value: i64 = sqlx::query_scalar(&format!("PRAGMA {}", name)).fetch_one(&mut *conn).await?;
+        sqlx::query(&format!("PRAGMA {} = {}", name, value)).execute(&mut *conn).await?;
+        previous.push((*name, old_value));
+    }
+    Ok(previous)
+}
+
+///Guesses the encoding of a file without a byte order mark: UTF-16 if every other byte of the start is NUL, UTF-8 if it is valid, or else the most likely legacy encoding.
+///UTF-16 is checked first since NUL bytes are valid UTF-8
+fn guess_encoding(bytes: &[u8]) -> &'static Encoding {
+    let sample = &bytes[..bytes.len().min(4096)];
+    let pairs = sample.len() / 2;
+    let even_nuls = sample.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd_nuls = sample.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+    if pairs > 0 && odd_nuls * 2 > pairs && even_nuls * 20 < pairs {
+        return UTF_16LE;
+    }
+    if pairs > 0 && even_nuls * 2 > pairs && odd_nuls * 20 < pairs {
+        return UTF_16BE;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+///Decodes a file as text, using its byte order mark if it has one and guessing the encoding if not, returning the text and the encoding's name
+pub fn decode_text(bytes: &[u8]) -> Result<(String, &'static str), EncodingError> {
+    let (encoding, bom_length) = Encoding::for_bom(bytes).unwrap_or_else(|| (guess_encoding(bytes), 0));
+    let text = encoding
+        .decode_without_bom_handling_and_without_replacement(&bytes[bom_length..])
+        .ok_or_else(|| EncodingError(format!("File is not valid {} text", encoding.name())))?;
+    if text.contains('\0') {
+        return Err(EncodingError("File looks like binary data, not text".to_string()));
+    }
+    Ok((text.into_owned(), encoding.name()))
+}
+
+///Starts reading, decoding and parsing an episode's file on the blocking thread pool, so decompressing it doesn't hold up the async workers.
+///The file's hash is recorded on the episode once it is read, and if it is the same as skip_hash the file isn't parsed at all
+fn start_parse(
+    source: &UploadSource,
+    episode: &mut PendingEpisode,
+    speakers: &Arc<SpeakerDetector>,
+    skip_hash: Option<&str>,
+) -> ParseTask {
+    match &mut episode.source {
+        EpisodeSource::File(entry) => {
+            let mut source = source.clone();
+            let entry = entry.clone();
+            let speakers = Arc::clone(speakers);
+            let skip_hash = skip_hash.map(str::to_string);
+            ParseTask::Parsing(tokio::task::spawn_blocking(move || {
+                let bytes = source.read(&entry)?;
+                let content_hash = content_hash(&bytes);
+                if skip_hash.as_deref() == Some(content_hash.as_str()) {
+                    return Ok(ReadFile { content_hash, parsed: None, parse_time: Duration::ZERO });
+                }
+                let parse_started = Instant::now();
+                let parsed = decode_text(&bytes).map(|(text, encoding)| {
+                    let (header, lines) = parse_transcript(Path::new(&entry), &text, &speakers);
+                    ParsedFile { lines, header, encoding }
+                });
+                Ok(ReadFile { content_hash, parsed: Some(parsed), parse_time: parse_started.elapsed() })
+            }))
+        }
+        EpisodeSource::Parsed(parsed) => ParseTask::Parsed(ParsedFile {
+            lines: std::mem::take(&mut parsed.lines),
+            header: std::mem::take(&mut parsed.header),
+            encoding: parsed.encoding,
+        }),
+    }
+}
+
+///Gets all transcript files in the upload, sorts them, gets episode data from the manifest or filename, then inserts the speakers + lines into the database
+///Files are read straight from the upload as they are needed, so nothing is written to disk
+///Progress is recorded on the job, and if the job is cancelled or fails the transaction is dropped so the dataset is left as it was
+pub async fn process_seasons(
+    pool: &SqlitePool,
+    source: &mut UploadSource,
+    _user_id: &str,
+    options: &IngestOptions,
+    job: &Job,
+) -> Result<IngestReport, Box<dyn std::error::Error>> {
+    //The connection goes back to the pool afterwards, so its pragmas are restored whether or not the load worked
+    let mut conn = pool.acquire().await?;
+    let has_episodes: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM episodes)").fetch_one(&mut *conn).await?;
+    let synchronous = if options.mode == UploadMode::Replace && !has_episodes {
+        FRESH_LOAD_SYNCHRONOUS
+    } else {
+        SAFE_LOAD_SYNCHRONOUS
+    };
+    let mut pragmas = BULK_LOAD_PRAGMAS.to_vec();
+    pragmas.push(("synchronous", synchronous));
+    let previous_pragmas = set_pragmas(&mut conn, &pragmas).await?;
+    let result = load_upload(&mut conn, source, options, job).await;
+    set_pragmas(&mut conn, &previous_pragmas).await?;
+    result
+}
+
+///Loads every episode in the upload in a single transaction, timing each stage for the report's throughput numbers
+async fn load_upload(
+    conn: &mut SqliteConnection,
+    source: &mut UploadSource,
+    options: &IngestOptions,
+    job: &Job,
+) -> Result<IngestReport, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let mut parse_time = Duration::ZERO;
+    let mut insert_time = Duration::ZERO;
+    let mut transaction = conn.begin().await?;
+    let mut report = IngestReport {
+        mode: options.mode,
+        ..Default::default()
+    };
+    let mut validation_errors = Vec::new();
+    let (names, unsupported_files) = source.entries();
+    report.skipped_files.extend(unsupported_files);
+
+    //A manifest at the archive root overrides the episode details parsed from filenames
+    let mut manifest_entries = HashMap::new();
+    let (manifests, mut entries): (Vec<String>, Vec<String>) = names.into_iter().partition(|name| is_manifest(name));
+    for manifest_file in &manifests {
+        let text = match decode_text(&source.read(manifest_file)?) {
+            Ok((text, _)) => text,
+            Err(err) => {
+                validation_errors.push(format!("{}: {}", manifest::normalize_path(manifest_file), err));
+                continue;
+            }
+        };
+        match manifest::parse(&manifest::normalize_path(manifest_file), &text) {
+            Ok(entries) => manifest_entries.extend(entries),
+            Err(errors) => validation_errors.extend(errors),
+        }
+    }
 
     if entries.is_empty() {
         return Err(Box::new(std::io::Error::new(
             std::io::ErrorKind::NotFound,
-            "No valid text files found",
+            "No valid transcript files found",
         )));
     }
 
-    entries.sort_by_key(|e| e.path().file_name().map(|n| n.to_os_string()));
-    let mut season_episodes: HashMap<i32, Vec<_>> = HashMap::new();
+    entries.sort_by_key(|name| Path::new(name).file_name().map(|n| n.to_os_string()));
+
+    //Hashes of the files the episodes were last loaded from, so the report can say what changed.
+    //A file its episode was already loaded from is skipped without parsing it, unless the upload asks to reprocess everything
+    let loaded_hashes = content_hashes(&mut *transaction).await?;
+    let skip_unchanged = !options.reprocess;
+    //Episodes a replace upload loads or keeps unchanged, every other episode is deleted once the upload is loaded.
+    //This all happens in the load's transaction, so a failed or cancelled load leaves the old dataset in place
+    let mut kept_episodes: HashSet<(i32, i32)> = HashSet::new();
+
+    //Structured and CSV/TSV files carry their own season + episode numbers, so they are read and parsed up front instead of using the filename, all at once on the blocking thread pool.
+    //A structured file's episodes are all loaded from the same hash, so a file with the hash of a loaded episode is unchanged and isn't parsed at all
+    let unchanged_hashes: Arc<HashSet<String>> =
+        Arc::new(loaded_hashes.values().filter(|_| skip_unchanged).cloned().collect());
+    let mut structured_tasks = HashMap::new();
+    for entry in &entries {
+        let Some(ext) = structured_extension(entry) else {
+            continue;
+        };
+        let filename = Path::new(&manifest::normalize_path(entry)).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let mut source = source.clone();
+        let path = entry.clone();
+        let unchanged_hashes = Arc::clone(&unchanged_hashes);
+        let csv_mapping = options.csv_mapping.clone();
+        let speakers = Arc::clone(&options.speaker_rules);
+        let task = tokio::task::spawn_blocking(move || {
+            let bytes = source.read(&path)?;
+            let content_hash = content_hash(&bytes);
+            if unchanged_hashes.contains(&content_hash) {
+                return Ok(ReadFile { content_hash, parsed: None, parse_time: Duration::ZERO });
+            }
+            let parse_started = Instant::now();
+            let parsed = decode_text(&bytes).map(|(text, encoding)| {
+                let mut episodes = match ext.as_str() {
+                    "csv" | "tsv" => delimited::parse(&filename, &text, &csv_mapping, ext == "tsv"),
+                    _ => structured::parse(&filename, &text, ext == "jsonl"),
+                };
+                if let Ok(episodes) = &mut episodes {
+                    for episode in episodes {
+                        speakers.split_speakers(&mut episode.lines);
+                        kinds::classify(&mut episode.lines);
+                    }
+                }
+                StructuredFile { episodes, encoding }
+            });
+            Ok::<_, ArchiveError>(ReadFile { content_hash, parsed: Some(parsed), parse_time: parse_started.elapsed() })
+        });
+        structured_tasks.insert(entry.clone(), task);
+    }
+
+    let mut season_episodes: HashMap<i32, Vec<PendingEpisode>> = HashMap::new();
+    let mut unnumbered_specials = Vec::new();
     for entry in &entries {
-        let filename = entry.file_name().to_string_lossy();
-        let parent_dir = entry.path().parent().and_then(|p| p.file_name()).map(|n| n.to_string_lossy());
+        let relative_path = manifest::normalize_path(entry);
+        let path = Path::new(&relative_path);
+        let filename = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let parent_dir = path.parent().and_then(|p| p.file_name()).map(|n| n.to_string_lossy());
+        let is_structured = structured_extension(entry).is_some();
+        let listed = manifest_entries.remove(&relative_path);
+        if listed.is_some() && is_structured {
+            add_warning(
+                &mut report,
+                job,
+                Some(relative_path.clone()),
+                "Listed in the manifest, but structured files use the season and episode numbers inside them".to_string(),
+            );
+        }
 
-        if let Some((season_num, episode_num, title)) =
-            parse_episode_filename(&filename, parent_dir.as_deref())
-        {
-            season_episodes.entry(season_num).or_default().push((episode_num, title, entry));
+        if let Some(listed) = listed.filter(|_| !is_structured) {
+            let title = listed
+                .title
+                .or_else(|| parse_episode_filename(&filename, parent_dir.as_deref()).map(|parsed| parsed.title))
+                .unwrap_or_default();
+            season_episodes.entry(listed.season).or_default().push(PendingEpisode {
+                file: relative_path,
+                number: listed.episode,
+                title,
+                details: EpisodeDetails {
+                    air_date: listed.air_date,
+                    writers: listed.writers,
+                    directors: listed.directors,
+                    synopsis: listed.synopsis,
+                    production_code: listed.production_code,
+                },
+                metadata: listed.metadata.map(|metadata| serde_json::Value::Object(metadata).to_string()),
+                number_end: listed.episode_end,
+                absolute_number: listed.absolute_number,
+                content_hash: None,
+                encoding: None,
+                source: EpisodeSource::File(entry.clone()),
+            });
+        } else if let Some(task) = structured_tasks.remove(entry) {
+            let read = task.await??;
+            parse_time += read.parse_time;
+            let hash = read.content_hash;
+            let Some(parsed) = read.parsed else {
+                kept_episodes.extend(loaded_hashes.iter().filter(|(_, loaded_hash)| **loaded_hash == hash).map(|(episode, _)| *episode));
+                report.unchanged_files.push(relative_path);
+                continue;
+            };
+            //A file that can't be decoded is left out on its own, rather than failing the whole upload
+            let StructuredFile { episodes, encoding } = match parsed {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    add_warning(&mut report, job, Some(relative_path.clone()), format!("Skipped: {}", err));
+                    report.skipped_files.push(SkippedFile { file: relative_path, reason: err.to_string() });
+                    continue;
+                }
+            };
+            match episodes {
+                Ok(episodes) => {
+                    for episode in episodes {
+                        season_episodes.entry(episode.season).or_default().push(PendingEpisode {
+                            file: relative_path.clone(),
+                            number: episode.episode,
+                            title: episode.title,
+                            details: EpisodeDetails::default(),
+                            metadata: None,
+                            number_end: None,
+                            absolute_number: None,
+                            content_hash: Some(hash.clone()),
+                            encoding: Some(encoding),
+                            source: EpisodeSource::Parsed(ParsedFile { lines: episode.lines, header: Header::default(), encoding }),
+                        });
+                    }
+                }
+                Err(errors) => validation_errors.extend(errors),
+            }
+        } else if let Some(parsed) = parse_episode_filename(&filename, parent_dir.as_deref()) {
+            let episode = PendingEpisode {
+                file: relative_path,
+                number: parsed.episode.unwrap_or_default(),
+                title: parsed.title,
+                details: EpisodeDetails::default(),
+                metadata: None,
+                number_end: parsed.episode_end,
+                absolute_number: parsed.absolute_number,
+                content_hash: None,
+                encoding: None,
+                source: EpisodeSource::File(entry.clone()),
+            };
+            match parsed.episode {
+                Some(_) => season_episodes.entry(parsed.season).or_default().push(episode),
+                None => unnumbered_specials.push(episode),
+            }
+        } else {
+            report.skipped_files.push(SkippedFile {
+                file: relative_path,
+                reason: "Filename does not match any episode pattern and the file is not listed in a manifest".to_string(),
+            });
         }
     }
+    if !validation_errors.is_empty() {
+        return Err(Box::new(ValidationErrors(validation_errors)));
+    }
+    //A replace upload numbers its specials as if season 0 were empty, since the specials it doesn't have are deleted
+    number_specials(&mut transaction, &mut season_episodes, unnumbered_specials, options.mode == UploadMode::Merge).await?;
+    let mut missing: Vec<_> = manifest_entries.into_values().map(|listed| listed.file).collect();
+    missing.sort();
+    for file in missing {
+        add_warning(&mut report, job, Some(file), "Listed in the manifest but was not found in the upload".to_string());
+    }
 
     let total_episodes: usize = season_episodes.values().map(|v| v.len()).sum();
     let mut episodes_processed = 0;
+    job.set_progress(0, total_episodes, None);
     let mut sorted_seasons: Vec<_> = season_episodes.into_iter().collect();
     sorted_seasons.sort_by_key(|(season_num, _)| *season_num);
 
-    for (season_num, mut episodes) in sorted_seasons {
+    let mut speakers = SpeakerCache::load(&mut transaction).await?;
 
-        //Adds season into database
-        let season_id: i64 = sqlx::query_scalar(
-            "INSERT INTO seasons (number) VALUES (?) ON CONFLICT(number) DO UPDATE SET number = excluded.number RETURNING id",
-        )
-        .bind(season_num)
+    //The FTS insert and delete triggers are dropped while loading. The new lines are indexed in one pass at the end,
+    //and deleted lines are taken out of the index a statement at a time, instead of row by row
+    let fts_triggers: Vec<(String, String)> =
+        sqlx::query_as("SELECT name, sql FROM sqlite_master WHERE type = 'trigger' AND name IN ('lines_ai', 'lines_ad')")
+            .fetch_all(&mut *transaction)
+            .await?;
+    for (name, _) in &fts_triggers {
+        sqlx::query(&format!("DROP TRIGGER {}", name)).execute(&mut *transaction).await?;
+    }
+    let last_line_id: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM lines")
         .fetch_one(&mut *transaction)
         .await?;
 
-        episodes.sort_by_key(|(num, title, _)| (*num, title.clone()));
-        for (episode_num, title, entry) in episodes {
-            episodes_processed += 1;
-
-            //should keep track of parsing progress in terminal
-            let progress = format!(
-                "\rProcessing episode {}/{}: S{:02}E{:02} {:<40}",
-                episodes_processed,
-                total_episodes,
-                season_num,
-                episode_num,
-                title
-            );
-            let _ = tokio_io::stdout().write_all(progress.as_bytes()).await;
-            let _ = tokio_io::stdout().flush().await;
-
-            //Adds episode associated with season into database
-            let episode_id: i64 = sqlx::query_scalar(
-                "INSERT INTO episodes (season_id, number, title) VALUES (?, ?, ?) ON CONFLICT(season_id, number) DO UPDATE SET title = excluded.title RETURNING id",
-            )
-            .bind(season_id)
-            .bind(episode_num)
-            .bind(&title)
-            .fetch_one(&mut *transaction)
-            .await?;
+    //Only the first file for each episode number is kept, the rest are reported as collisions
+    let mut kept: Vec<(i32, PendingEpisode)> = Vec::new();
+    for (season_num, mut episodes) in sorted_seasons {
+        episodes.sort_by_key(|episode| (episode.number, episode.title.clone()));
 
-            let file = File::open(entry.path()).await?;
-            let mut reader = BufReader::new(file).lines();
-            let mut line_num = 1;
-
-            //Iterates through each line in the text file and inserts the line and speaker into the database
-            while let Some(line_result) = reader.next_line().await? {
-                let line = line_result;
-                let (speaker_id, content) = if let Some((speaker, content)) = line.split_once(':') {
-                    let speaker_id: i64 = sqlx::query_scalar(
-                        "INSERT INTO speakers (name) VALUES (?) ON CONFLICT(name) DO UPDATE SET name = excluded.name RETURNING id",
-                    )
-                    .bind(speaker.trim())
-                    .fetch_one(&mut *transaction)
-                    .await?;
-                    (Some(speaker_id), content.trim().to_string())
-                } else {
-                    (None, line.trim().to_string())
-                };
+        for episode in episodes {
+            match kept.last() {
+                Some((previous_season, previous)) if *previous_season == season_num && previous.number == episode.number => {
+                    let kept_file = previous.file.clone();
+                    match report.collisions.iter_mut().find(|c| c.season == season_num && c.episode == episode.number) {
+                        Some(collision) => collision.skipped_files.push(episode.file.clone()),
+                        None => report.collisions.push(EpisodeCollision {
+                            season: season_num,
+                            episode: episode.number,
+                            kept_file: kept_file.clone(),
+                            skipped_files: vec![episode.file.clone()],
+                        }),
+                    }
+                    report.skipped_files.push(SkippedFile {
+                        file: episode.file,
+                        reason: format!("Season {} episode {} was already loaded from {}", season_num, episode.number, kept_file),
+                    });
+                    episodes_processed += 1;
+                }
+                _ => kept.push((season_num, episode)),
+            }
+        }
+    }
+
+    //Episodes are parsed a few ahead of the one being written, so parsing runs in parallel while the writes stay in order on the one connection
+    let parse_ahead = std::thread::available_parallelism().map_or(4, |n| n.get()) * 2;
+    let mut season_ids: HashMap<i32, i64> = HashMap::new();
+    let mut pending = kept.into_iter();
+    let mut parsing: VecDeque<(i32, PendingEpisode, ParseTask)> = VecDeque::new();
+    loop {
+        while parsing.len() < parse_ahead {
+            let Some((season_num, mut episode)) = pending.next() else {
+                break;
+            };
+            let skip_hash = loaded_hashes.get(&(season_num, episode.number)).filter(|_| skip_unchanged);
+            let task = start_parse(source, &mut episode, &options.speaker_rules, skip_hash.map(String::as_str));
+            parsing.push_back((season_num, episode, task));
+        }
+        let Some((season_num, mut episode, task)) = parsing.pop_front() else {
+            break;
+        };
+        job.check_cancelled()?;
+        job.set_progress(
+            episodes_processed,
+            total_episodes,
+            Some(format!("S{:02}E{:02} {}", season_num, episode.number, episode.title).trim_end().to_string()),
+        );
+        episodes_processed += 1;
+
+        job.emit(ProgressEvent::FileStarted {
+            file: episode.file.clone(),
+            season: season_num,
+            episode: episode.number,
+            title: episode.title.clone(),
+        });
 
-                sqlx::query("INSERT INTO lines (season_id, episode_id, speaker_id, line_number, content) VALUES (?, ?, ?, ?, ?)")
-                    .bind(season_id)
-                    .bind(episode_id)
-                    .bind(speaker_id)
-                    .bind(line_num)
-                    .bind(&content)
-                    .execute(&mut *transaction)
-                    .await?;
+        //A file that can't be decoded is left out on its own, before anything of its episode is written
+        let parsed = match task {
+            ParseTask::Parsing(handle) => {
+                let read = handle.await??;
+                parse_time += read.parse_time;
+                episode.content_hash = Some(read.content_hash);
+                read.parsed
+            }
+            ParseTask::Parsed(parsed) => Some(Ok(parsed)),
+        };
+        let Some(parsed) = parsed else {
+            kept_episodes.insert((season_num, episode.number));
+            report.unchanged_files.push(episode.file.clone());
+            continue;
+        };
+        let parsed = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                add_warning(&mut report, job, Some(episode.file.clone()), format!("Skipped: {}", err));
+                report.skipped_files.push(SkippedFile { file: episode.file.clone(), reason: err.to_string() });
+                continue;
+            }
+        };
+        //The manifest's details come first, then the ones in the file's header block
+        episode.details = std::mem::take(&mut episode.details).or(parsed.header.details);
+        episode.encoding = Some(parsed.encoding);
+        if episode.title.is_empty() {
+            episode.title = parsed.header.title.unwrap_or_default();
+        }
+        for warning in parsed.header.warnings {
+            add_warning(&mut report, job, Some(episode.file.clone()), warning);
+        }
 
-                line_num += 1;
+        //Adds season and episode associated with season into database
+        let insert_started = Instant::now();
+        let season_id = match season_ids.get(&season_num) {
+            Some(season_id) => *season_id,
+            None => {
+                let season_id = upsert_season(&mut transaction, season_num).await?;
+                season_ids.insert(season_num, season_id);
+                season_id
             }
+        };
+        //An episode that is already loaded has its old lines and scenes deleted. Merging keeps the details the new file doesn't have,
+        //while a replace upload deletes the whole episode so nothing of the old one is kept
+        let existing_episode = find_episode(&mut transaction, season_id, episode.number).await?;
+        match existing_episode {
+            Some(existing_id) if options.mode == UploadMode::Replace => delete_episode_row(&mut transaction, existing_id).await?,
+            Some(existing_id) => delete_episode_lines(&mut transaction, existing_id).await?,
+            None => {}
         }
+        kept_episodes.insert((season_num, episode.number));
+        let episode_id = upsert_episode(&mut transaction, season_id, &episode).await?;
+        insert_time += insert_started.elapsed();
+        let mut parsed_lines = parsed.lines;
+        let scenes = scenes::detect(&mut parsed_lines);
+
+        let episode_report = EpisodeReport {
+            season: season_num,
+            episode: episode.number,
+            title: episode.title.clone(),
+            file: episode.file.clone(),
+            lines: parsed_lines.len(),
+            lines_without_speaker: parsed_lines.iter().filter(|line| line.speaker.is_none()).count(),
+            replaced: existing_episode.is_some(),
+            encoding: parsed.encoding.to_string(),
+            scenes: scenes.len(),
+            content_hash: episode.content_hash.clone().unwrap_or_default(),
+            change: episode_change(
+                loaded_hashes.get(&(season_num, episode.number)).map(String::as_str),
+                episode.content_hash.as_deref().unwrap_or_default(),
+            ),
+        };
+        if parsed_lines.is_empty() {
+            add_warning(&mut report, job, Some(episode.file.clone()), "No lines were found in this file".to_string());
+        } else if episode_report.lines_without_speaker == episode_report.lines {
+            add_warning(&mut report, job, Some(episode.file.clone()), "No line in this file has a speaker".to_string());
+        } else if episode_report.lines_without_speaker * 2 > episode_report.lines {
+            add_warning(
+                &mut report,
+                job,
+                Some(episode.file.clone()),
+                format!("{} of {} lines have no speaker", episode_report.lines_without_speaker, episode_report.lines),
+            );
+        }
+        report.total_lines += episode_report.lines;
+        report.lines_without_speaker += episode_report.lines_without_speaker;
+
+        //Inserts the parsed lines and their speakers into the database
+        let insert_started = Instant::now();
+        insert_lines(&mut transaction, &mut speakers, season_id, episode_id, &parsed_lines).await?;
+        insert_scenes(&mut transaction, episode_id, &scenes).await?;
+        insert_time += insert_started.elapsed();
+        if episode_report.replaced {
+            report.episodes_replaced += 1;
+        } else {
+            report.episodes_created += 1;
+        }
+        report.episodes.push(episode_report);
     }
 
-    let _ = tokio_io::stdout().write_all(b"\nDone parsing.\n").await;
-    let _ = tokio_io::stdout().flush().await;
+    job.check_cancelled()?;
+    if options.mode == UploadMode::Replace {
+        delete_episodes_except(&mut transaction, &kept_episodes).await?;
+    }
+    let index_started = Instant::now();
+    sqlx::query("INSERT INTO lines_fts(rowid, content) SELECT id, content FROM lines WHERE id > ?")
+        .bind(last_line_id)
+        .execute(&mut *transaction)
+        .await?;
+    for (_, sql) in fts_triggers {
+        sqlx::query(&sql).execute(&mut *transaction).await?;
+    }
+    let index_time = index_started.elapsed();
+
+    //Replacing episodes can remove every line a speaker had
+    remove_orphan_speakers(&mut transaction).await?;
+    job.set_progress(episodes_processed, total_episodes, None);
     transaction.commit().await?;
-    tokio::fs::remove_dir_all(extract_dir).await?;
-    Ok(())
+
+    let elapsed = started.elapsed();
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    report.throughput = IngestThroughput {
+        elapsed_ms: elapsed.as_millis() as u64,
+        parse_ms: parse_time.as_millis() as u64,
+        insert_ms: insert_time.as_millis() as u64,
+        index_ms: index_time.as_millis() as u64,
+        lines_per_second: (report.total_lines as f64 / seconds).round(),
+        episodes_per_second: (report.episodes.len() as f64 / seconds * 100.0).round() / 100.0,
+    };
+    Ok(report)
+}
+
+///Replaces one episode's transcript with a single uploaded file, adding the season and episode if they don't exist yet.
+///The title comes from the given title, then the filename, then the file's header block, then the episode's current title
+pub async fn replace_episode(
+    pool: &SqlitePool,
+    season_num: i32,
+    episode_num: i32,
+    title: Option<String>,
+    file_name: &str,
+    contents: Vec<u8>,
+    speakers: SpeakerDetector,
+) -> Result<EpisodeReport, Box<dyn std::error::Error>> {
+    //Decoding and parsing a whole transcript is CPU bound, so it runs on the blocking thread pool instead of the request's worker
+    let path = file_name.to_string();
+    let (hash, encoding, header, parsed_lines, scenes) = tokio::task::spawn_blocking(move || {
+        let (text, encoding) = decode_text(&contents)?;
+        let (header, mut parsed_lines) = parse_transcript(Path::new(&path), &text, &speakers);
+        let scenes = scenes::detect(&mut parsed_lines);
+        Ok::<_, EncodingError>((content_hash(&contents), encoding, header, parsed_lines, scenes))
+    })
+    .await??;
+
+    let mut transaction = pool.begin().await?;
+    let season_id = upsert_season(&mut transaction, season_num).await?;
+    //A number inside a multi-part episode like 3x01-02 replaces that episode, which keeps its numbering
+    let existing_episode: Option<Episode> = sqlx::query_as(
+        "SELECT * FROM episodes WHERE season_id = ?1 AND number <= ?2 AND COALESCE(number_end, number) >= ?2 ORDER BY number DESC LIMIT 1",
+    )
+    .bind(season_id)
+    .bind(episode_num)
+    .fetch_optional(&mut *transaction)
+    .await?;
+    if let Some(existing) = &existing_episode {
+        delete_episode_lines(&mut transaction, existing.id).await?;
+    }
+    let parsed_filename = parse_episode_filename(file_name, None);
+    let title = title
+        .filter(|title| !title.trim().is_empty())
+        .or_else(|| parsed_filename.as_ref().map(|parsed| parsed.title.clone()).filter(|title| !title.is_empty()))
+        .or(header.title)
+        .or_else(|| existing_episode.as_ref().map(|existing| existing.title.clone()))
+        .unwrap_or_default();
+    //A new episode takes its range and absolute number from the filename, if the filename is for the same episode
+    let (number, number_end, absolute_number) = match &existing_episode {
+        Some(existing) => (existing.number, existing.number_end, existing.absolute_number),
+        None => match parsed_filename.filter(|parsed| parsed.episode == Some(episode_num)) {
+            Some(parsed) => (episode_num, parsed.episode_end, parsed.absolute_number),
+            None => (episode_num, None, None),
+        },
+    };
+
+    let episode = PendingEpisode {
+        file: file_name.to_string(),
+        number,
+        title,
+        details: header.details,
+        metadata: None,
+        number_end,
+        absolute_number,
+        content_hash: Some(hash),
+        encoding: Some(encoding),
+        source: EpisodeSource::File(file_name.to_string()),
+    };
+    let episode_id = upsert_episode(&mut transaction, season_id, &episode).await?;
+    let mut speakers = SpeakerCache::load(&mut transaction).await?;
+    insert_lines(&mut transaction, &mut speakers, season_id, episode_id, &parsed_lines).await?;
+    insert_scenes(&mut transaction, episode_id, &scenes).await?;
+    remove_orphan_speakers(&mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(EpisodeReport {
+        season: season_num,
+        episode: episode.number,
+        title: episode.title,
+        file: episode.file,
+        lines: parsed_lines.len(),
+        lines_without_speaker: parsed_lines.iter().filter(|line| line.speaker.is_none()).count(),
+        replaced: existing_episode.is_some(),
+        encoding: encoding.to_string(),
+        scenes: scenes.len(),
+        change: episode_change(
+            existing_episode.as_ref().and_then(|existing| existing.content_hash.as_deref()),
+            episode.content_hash.as_deref().unwrap_or_default(),
+        ),
+        content_hash: episode.content_hash.unwrap_or_default(),
+    })
+}
+
+///Deletes the lines of a season's episodes (or just one of them), then the episodes themselves and any speakers left without lines.
+///Lines are deleted explicitly rather than by cascade so the delete trigger keeps the FTS index in step
+async fn delete_episodes(
+    conn: &mut SqliteConnection,
+    season_num: i32,
+    episode_num: Option<i32>,
+) -> Result<DeleteSummary, sqlx::Error> {
+    let episode_ids = "SELECT e.id FROM episodes e JOIN seasons s ON e.season_id = s.id WHERE s.number = ?1 AND (?2 IS NULL OR (e.number <= ?2 AND COALESCE(e.number_end, e.number) >= ?2))";
+    let lines = sqlx::query(&format!("DELETE FROM lines WHERE episode_id IN ({})", episode_ids))
+        .bind(season_num)
+        .bind(episode_num)
+        .execute(&mut *conn)
+        .await?;
+    let episodes = sqlx::query(&format!("DELETE FROM episodes WHERE id IN ({})", episode_ids))
+        .bind(season_num)
+        .bind(episode_num)
+        .execute(&mut *conn)
+        .await?;
+    Ok(DeleteSummary {
+        episodes_deleted: episodes.rows_affected(),
+        lines_deleted: lines.rows_affected(),
+        speakers_removed: remove_orphan_speakers(conn).await?,
+    })
+}
+
+///Deletes an episode and its lines, returning None if the episode doesn't exist
+pub async fn delete_episode(pool: &SqlitePool, season_num: i32, episode_num: i32) -> Result<Option<DeleteSummary>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let summary = delete_episodes(&mut transaction, season_num, Some(episode_num)).await?;
+    if summary.episodes_deleted == 0 {
+        return Ok(None);
+    }
+    transaction.commit().await?;
+    Ok(Some(summary))
+}
+
+///Deletes a season with all of its episodes and lines, returning None if the season doesn't exist
+pub async fn delete_season(pool: &SqlitePool, season_num: i32) -> Result<Option<DeleteSummary>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let summary = delete_episodes(&mut transaction, season_num, None).await?;
+    let seasons = sqlx::query("DELETE FROM seasons WHERE number = ?")
+        .bind(season_num)
+        .execute(&mut *transaction)
+        .await?;
+    if seasons.rows_affected() == 0 {
+        return Ok(None);
+    }
+    transaction.commit().await?;
+    Ok(Some(summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn utf16be(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn parse_timecode_reads_subtitle_formats() {
+        assert_eq!(parse_timecode("00:01:02,345"), Some(62_345));
+        assert_eq!(parse_timecode("01:00:00.000"), Some(3_600_000));
+        assert_eq!(parse_timecode("01:02.5"), Some(62_500));
+        //ASS timecodes have centiseconds
+        assert_eq!(parse_timecode("0:00:01.50"), Some(1_500));
+        assert_eq!(parse_timecode(" 00:00:01,000 "), Some(1_000));
+    }
+
+    #[test]
+    fn parse_timecode_rejects_malformed_timecodes() {
+        assert_eq!(parse_timecode("00:00:01"), None);
+        assert_eq!(parse_timecode("00:00:01,"), None);
+        assert_eq!(parse_timecode("00:00:01,0001"), None);
+        assert_eq!(parse_timecode("1:2:3:4.000"), None);
+        assert_eq!(parse_timecode("aa:bb.000"), None);
+    }
+
+    #[test]
+    fn parse_timecode_rejects_signs_and_overflow() {
+        assert_eq!(parse_timecode("-1:00:00,000"), None);
+        assert_eq!(parse_timecode("00:+1:00,000"), None);
+        assert_eq!(parse_timecode("00:00:01,-50"), None);
+        assert_eq!(parse_timecode("00: 01:02,000"), None);
+        assert_eq!(parse_timecode("9223372036854775807:00:00,000"), None);
+        assert_eq!(parse_timecode("99999999999999999999:00,000"), None);
+    }
+
+    #[test]
+    fn split_blocks_separates_on_blank_lines() {
+        let blocks = split_blocks("\n1\n00:00:01,000 --> 00:00:02,000\nHi\n\n  \n2\nBye\n");
+        assert_eq!(blocks, vec![vec!["1", "00:00:01,000 --> 00:00:02,000", "Hi"], vec!["2", "Bye"]]);
+    }
+
+    #[test]
+    fn parse_plain_text_splits_speakers() {
+        let lines = parse_plain_text("JERRY: Hello.\nNote: 10:30 PM\nThe door opens.", &SpeakerDetector::default());
+        let parsed: Vec<_> = lines.iter().map(|line| (line.speaker.as_deref(), line.content.as_str())).collect();
+        assert_eq!(parsed, vec![(Some("JERRY"), "Hello."), (None, "Note: 10:30 PM"), (None, "The door opens.")]);
+    }
+
+    ///Season, episode, last episode, absolute number and title parsed from a filename
+    type ParsedFilename = (i32, Option<i32>, Option<i32>, Option<i32>, String);
+
+    fn filename(name: &str, parent_dir: Option<&str>) -> Option<ParsedFilename> {
+        parse_episode_filename(name, parent_dir)
+            .map(|parsed| (parsed.season, parsed.episode, parsed.episode_end, parsed.absolute_number, parsed.title))
+    }
+
+    #[test]
+    fn parse_episode_filename_reads_season_and_episode() {
+        assert_eq!(filename("S03E01 - The Pilot.txt", None), Some((3, Some(1), None, None, "The Pilot".to_string())));
+        assert_eq!(filename("3x01 - The Pilot.srt", None), Some((3, Some(1), None, None, "The Pilot".to_string())));
+        assert_eq!(filename("s03e01.txt", None), Some((3, Some(1), None, None, String::new())));
+        assert_eq!(filename("E01 - The Pilot.txt", Some("Season 3")), Some((3, Some(1), None, None, "The Pilot".to_string())));
+        assert_eq!(filename("Notes.txt", Some("Season 3")), None);
+    }
+
+    #[test]
+    fn parse_episode_filename_reads_multi_part_episodes() {
+        assert_eq!(filename("S03E01E02 - The Pilot.txt", None), Some((3, Some(1), Some(2), None, "The Pilot".to_string())));
+        assert_eq!(filename("S03E01-E02.txt", None), Some((3, Some(1), Some(2), None, String::new())));
+        assert_eq!(filename("S03E01-02.txt", None), Some((3, Some(1), Some(2), None, String::new())));
+        assert_eq!(filename("3x01-02 - The Pilot.txt", None), Some((3, Some(1), Some(2), None, "The Pilot".to_string())));
+        assert_eq!(filename("3x01-3x02 - The Pilot.txt", None), Some((3, Some(1), Some(2), None, "The Pilot".to_string())));
+        //A range that ends before it starts is a single episode
+        assert_eq!(filename("S03E05-02.txt", None), Some((3, Some(5), None, None, String::new())));
+    }
+
+    #[test]
+    fn parse_episode_filename_reads_absolute_numbers() {
+        assert_eq!(filename("E123 - The Finale.txt", None), Some((1, Some(123), None, Some(123), "The Finale".to_string())));
+        assert_eq!(filename("Episode 7.txt", Some("transcripts")), Some((1, Some(7), None, Some(7), String::new())));
+    }
+
+    #[test]
+    fn parse_episode_filename_reads_specials() {
+        assert_eq!(filename("S00E01.txt", None), Some((0, Some(1), None, None, String::new())));
+        assert_eq!(filename("S00E01.txt", Some("Specials")), Some((0, Some(1), None, None, String::new())));
+        assert_eq!(filename("E02 - Outtakes.txt", Some("Specials")), Some((0, Some(2), None, None, "Outtakes".to_string())));
+        assert_eq!(filename("Special - Christmas.txt", None), Some((0, None, None, None, "Christmas".to_string())));
+        assert_eq!(filename("Special 3 - Christmas.txt", None), Some((0, Some(3), None, None, "Christmas".to_string())));
+        assert_eq!(filename("Christmas.txt", Some("Specials")), Some((0, None, None, None, "Christmas".to_string())));
+    }
+
+    #[test]
+    fn decode_text_reads_utf8() {
+        let (text, encoding) = decode_text("JERRY: Café?\n".as_bytes()).unwrap();
+        assert_eq!(text, "JERRY: Café?\n");
+        assert_eq!(encoding, "UTF-8");
+    }
+
+    #[test]
+    fn decode_text_strips_byte_order_marks() {
+        let (text, encoding) = decode_text(b"\xEF\xBB\xBFJERRY: Hello").unwrap();
+        assert_eq!((text.as_str(), encoding), ("JERRY: Hello", "UTF-8"));
+
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(utf16le("JERRY: Hello"));
+        let (text, encoding) = decode_text(&bytes).unwrap();
+        assert_eq!((text.as_str(), encoding), ("JERRY: Hello", "UTF-16LE"));
+    }
+
+    #[test]
+    fn decode_text_detects_utf16_without_byte_order_mark() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nJERRY: Hello\n";
+        let (text, encoding) = decode_text(&utf16le(srt)).unwrap();
+        assert_eq!((text.as_str(), encoding), (srt, "UTF-16LE"));
+
+        let (text, encoding) = decode_text(&utf16be(srt)).unwrap();
+        assert_eq!((text.as_str(), encoding), (srt, "UTF-16BE"));
+    }
+
+    #[test]
+    fn decode_text_detects_legacy_encodings() {
+        let bytes = b"GEORGE: I was in the pool! The water was cold, it was very cold. Caf\xE9 na\xEFve r\xE9sum\xE9.\n";
+        let (text, encoding) = decode_text(bytes).unwrap();
+        assert_eq!(encoding, "windows-1252");
+        assert!(text.contains("Café naïve résumé"));
+    }
+
+    #[test]
+    fn decode_text_rejects_binary_data() {
+        assert!(decode_text(&[0x00, 0x00, 0x00, 0x01, 0x89, 0x50, 0x4E, 0x47, 0x00, 0x00]).is_err());
+    }
 }
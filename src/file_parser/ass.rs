@@ -0,0 +1,90 @@
+use super::{parse_timecode, ParsedLine};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+///Default [Events] columns for files that omit the Format line (ASS v4+ order)
+const DEFAULT_FORMAT: &[&str] = &["layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text"];
+
+lazy_static! {
+    static ref OVERRIDE_REGEX: Regex = Regex::new(r"\{[^}]*\}").unwrap();
+}
+
+///Removes {\...} override blocks and turns ASS line break/hard space escapes into spaces
+fn clean_event_text(text: &str) -> String {
+    let text = OVERRIDE_REGEX.replace_all(text, "");
+    let text = text.replace("\\N", " ").replace("\\n", " ").replace("\\h", " ");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+///Parses an ASS/SSA subtitle file, using each Dialogue event's Name/Actor field as the speaker
+pub fn parse(text: &str) -> Vec<ParsedLine> {
+    let mut lines = Vec::new();
+    let mut in_events = false;
+    let mut format: Vec<String> = DEFAULT_FORMAT.iter().map(|field| field.to_string()).collect();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        let Some((kind, value)) = line.split_once(':') else {
+            continue;
+        };
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "format" => {
+                format = value.split(',').map(|field| field.trim().to_ascii_lowercase()).collect();
+            }
+            "dialogue" => {
+                //Text is always the last field and can itself contain commas
+                let fields: Vec<&str> = value.trim_start().splitn(format.len(), ',').collect();
+                let field = |name: &str| {
+                    format
+                        .iter()
+                        .position(|field| field == name)
+                        .and_then(|index| fields.get(index))
+                        .map(|value| value.trim())
+                        .filter(|value| !value.is_empty())
+                };
+                let Some(raw_text) = field("text") else {
+                    continue;
+                };
+                let content = clean_event_text(raw_text);
+                if content.is_empty() {
+                    continue;
+                }
+                lines.push(ParsedLine {
+                    speaker: field("name").or_else(|| field("actor")).map(str::to_string),
+                    content,
+                    start_ms: field("start").and_then(parse_timecode),
+                    end_ms: field("end").and_then(parse_timecode),
+                    style: field("style").map(|style| style.trim_start_matches('*').to_string()),
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_name_field_as_speaker() {
+        let text = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                    Dialogue: 0,0:00:01.50,0:00:03.00,*Default,Jerry,0,0,0,,{\\i1}Hello,\\Nthere{\\i0}\n\
+                    Dialogue: 0,0:00:04.00,0:00:05.00,Default,,0,0,0,,{\\pos(1,1)}\n";
+        let lines = parse(text);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].speaker.as_deref(), Some("Jerry"));
+        assert_eq!(lines[0].content, "Hello, there");
+        assert_eq!((lines[0].start_ms, lines[0].end_ms), (Some(1500), Some(3000)));
+        assert_eq!(lines[0].style.as_deref(), Some("Default"));
+    }
+}
@@ -0,0 +1,155 @@
+use super::structured::StructuredEpisode;
+use super::ParsedLine;
+use crate::models::{CsvColumn, CsvColumnMapping};
+use csv::{ReaderBuilder, StringRecord};
+use std::collections::BTreeMap;
+
+///Finds the index of a mapped column, falling back to a header named after the field when it isn't mapped
+fn resolve_column(
+    column: Option<&CsvColumn>,
+    field: &str,
+    headers: Option<&StringRecord>,
+    required: bool,
+) -> Result<Option<usize>, String> {
+    let find_header = |name: &str| headers.and_then(|headers| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name)));
+    match column {
+        Some(CsvColumn::Index(index)) => Ok(Some(*index)),
+        Some(CsvColumn::Name(name)) => find_header(name)
+            .map(Some)
+            .ok_or_else(|| format!("column \"{}\" mapped to {} was not found in the header row", name, field)),
+        None => match find_header(field) {
+            Some(index) => Ok(Some(index)),
+            None if required => Err(format!("no column is mapped to {}", field)),
+            None => Ok(None),
+        },
+    }
+}
+
+///Parses a CSV/TSV transcript where each row is one line, grouping rows into episodes by their season + episode columns
+pub fn parse(file_name: &str, text: &str, mapping: &CsvColumnMapping, tab_separated: bool) -> Result<Vec<StructuredEpisode>, Vec<String>> {
+    let delimiter = mapping.delimiter.unwrap_or(if tab_separated { '\t' } else { ',' });
+    if !delimiter.is_ascii() {
+        return Err(vec![format!("{}: delimiter must be a single ASCII character", file_name)]);
+    }
+    let has_headers = mapping.has_headers.unwrap_or(true);
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .has_headers(has_headers)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = if has_headers {
+        match reader.headers() {
+            Ok(headers) => Some(headers.clone()),
+            Err(err) => return Err(vec![format!("{}: could not read the header row: {}", file_name, err)]),
+        }
+    } else {
+        None
+    };
+    let columns = [
+        ("season", mapping.season.as_ref(), true),
+        ("episode", mapping.episode.as_ref(), true),
+        ("title", mapping.title.as_ref(), false),
+        ("speaker", mapping.speaker.as_ref(), false),
+        ("content", mapping.content.as_ref(), true),
+        ("line_number", mapping.line_number.as_ref(), false),
+    ];
+    let mut resolved = Vec::new();
+    let mut errors = Vec::new();
+    for (field, column, required) in columns {
+        match resolve_column(column, field, headers.as_ref(), required) {
+            Ok(index) => resolved.push(index),
+            Err(err) => errors.push(format!("{}: {}", file_name, err)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let [season_col, episode_col, title_col, speaker_col, content_col, line_number_col] = resolved[..] else {
+        unreachable!();
+    };
+
+    //Rows are grouped by (season, episode), and every row is checked so all problems are reported at once
+    let mut episodes: BTreeMap<(i32, i32), StructuredEpisode> = BTreeMap::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(format!("{}: {}", file_name, err));
+                continue;
+            }
+        };
+        let row = format!("{} row {}", file_name, record.position().map_or(0, |position| position.line()));
+        let value = |index: Option<usize>| index.and_then(|index| record.get(index)).map(str::trim).filter(|value| !value.is_empty());
+        let mut row_errors = Vec::new();
+        let mut number = |field: &str, index: Option<usize>, required: bool| -> Option<i32> {
+            match value(index) {
+                Some(raw) => match raw.parse::<i32>() {
+                    Ok(number) if number >= 0 => Some(number),
+                    _ => {
+                        row_errors.push(format!("{}: {} \"{}\" is not a valid number", row, field, raw));
+                        None
+                    }
+                },
+                None if required => {
+                    row_errors.push(format!("{}: {} is missing", row, field));
+                    None
+                }
+                None => None,
+            }
+        };
+        let season = number("season", season_col, true);
+        let episode = number("episode", episode_col, true);
+        let line_number = number("line_number", line_number_col, line_number_col.is_some());
+        let content = value(content_col);
+        if content.is_none() {
+            row_errors.push(format!("{}: content is missing", row));
+        }
+        let (Some(season), Some(episode), Some(content), true) = (season, episode, content, row_errors.is_empty()) else {
+            errors.extend(row_errors);
+            continue;
+        };
+
+        let title = value(title_col).unwrap_or("");
+        let entry = episodes.entry((season, episode)).or_insert_with(|| StructuredEpisode {
+            season,
+            episode,
+            title: title.to_string(),
+            lines: Vec::new(),
+        });
+        if entry.title.is_empty() {
+            entry.title = title.to_string();
+        } else if !title.is_empty() && entry.title != title {
+            errors.push(format!(
+                "{}: title \"{}\" does not match \"{}\" given earlier for season {} episode {}",
+                row, title, entry.title, season, episode
+            ));
+            continue;
+        }
+        if line_number.is_some() && entry.lines.iter().any(|line| line.line_number == line_number) {
+            errors.push(format!(
+                "{}: line_number {} is used twice in season {} episode {}",
+                row,
+                line_number.unwrap_or_default(),
+                season,
+                episode
+            ));
+            continue;
+        }
+        entry.lines.push(ParsedLine {
+            speaker: value(speaker_col).map(str::to_string),
+            content: content.to_string(),
+            line_number,
+            ..Default::default()
+        });
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut episodes: Vec<StructuredEpisode> = episodes.into_values().collect();
+    for episode in &mut episodes {
+        episode.lines.sort_by_key(|line| line.line_number);
+    }
+    Ok(episodes)
+}
@@ -0,0 +1,211 @@
+use super::{split_speaker, ParsedLine, SpeakerDetector};
+use crate::models::LineKind;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    //Checked for every line of every plain text transcript, so it is only compiled once
+    static ref SCENE_REGEX: Regex = Regex::new(r"(?i)^(?:int\.?/ext|int/ext|i/e|int|ext|est)[.\s]").unwrap();
+    static ref BONEYARD_REGEX: Regex = Regex::new(r"(?s)/\*.*?\*/").unwrap();
+    static ref NOTE_REGEX: Regex = Regex::new(r"(?s)\[\[.*?\]\]").unwrap();
+    //Scene numbers like #1A# at the end of a heading
+    static ref NUMBER_REGEX: Regex = Regex::new(r"\s*#[^#]*#\s*$").unwrap();
+    static ref TITLE_PAGE_REGEX: Regex =
+        Regex::new(r"(?i)^(?:title|credit|authors?|source|draft date|date|contact|copyright|notes|revision)\s*:").unwrap();
+}
+
+///Checks if a line is a scene heading (INT./EXT. sluglines or a forced ".HEADING")
+pub(super) fn is_scene_heading(line: &str) -> bool {
+    (line.starts_with('.') && !line.starts_with("..")) || SCENE_REGEX.is_match(line)
+}
+
+///Gets the speaker label from a character cue, dropping only the Fountain markers (forced "@", dual dialogue "^" and "#1#" numbers).
+///Extensions like (V.O.) are kept, so the speaker detector strips the ones it is configured to and the raw label keeps them
+fn character_name(line: &str) -> Option<String> {
+    let forced = line.strip_prefix('@');
+    let cue = forced.unwrap_or(line).trim_end_matches('^').trim();
+    let cue = NUMBER_REGEX.replace(cue, "").trim().to_string();
+    let name = cue.split('(').next().unwrap_or("").trim();
+    if name.is_empty() || !name.chars().any(char::is_alphabetic) {
+        return None;
+    }
+    if forced.is_some() {
+        return Some(cue);
+    }
+    let is_cue = name == name.to_uppercase() && !name.ends_with("TO:") && !name.ends_with(':') && !is_scene_heading(name);
+    is_cue.then_some(cue)
+}
+
+///Checks if a line is a parenthetical like "(beat)"
+fn is_parenthetical(line: &str) -> bool {
+    line.starts_with('(') && line.ends_with(')')
+}
+
+///Removes /* boneyard */ sections and [[notes]]
+fn strip_comments(text: &str) -> String {
+    let text = BONEYARD_REGEX.replace_all(text, "");
+    NOTE_REGEX.replace_all(&text, "").to_string()
+}
+
+///Guesses if a plain text transcript is screenplay formatted, by comparing character cue blocks to "Speaker: content" lines
+pub fn looks_like_screenplay(text: &str, speakers: &SpeakerDetector) -> bool {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let mut cues = 0;
+    let mut headings = 0;
+    let mut colon_lines = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let after_blank = i == 0 || lines[i - 1].is_empty();
+        let before_text = lines.get(i + 1).is_some_and(|next| !next.is_empty());
+        if after_blank && is_scene_heading(line) {
+            headings += 1;
+        } else if after_blank && before_text && !line.starts_with('@') && character_name(line).is_some() {
+            cues += 1;
+        } else if split_speaker(line, speakers).0.is_some() {
+            colon_lines += 1;
+        }
+    }
+    cues >= 2 && cues + headings > colon_lines
+}
+
+///Parses a Fountain/screenplay transcript, attaching dialogue to the preceding character cue
+pub fn parse(text: &str) -> Vec<ParsedLine> {
+    let text = strip_comments(text);
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let mut parsed: Vec<ParsedLine> = Vec::new();
+    let mut scene_heading: Option<String> = None;
+    let mut i = 0;
+
+    //Skips the title page, which is a block of "Key: value" lines at the very top
+    if lines.first().is_some_and(|line| TITLE_PAGE_REGEX.is_match(line)) {
+        while i < lines.len() && !lines[i].is_empty() {
+            i += 1;
+        }
+    }
+
+    while i < lines.len() {
+        let line = lines[i];
+        let after_blank = i == 0 || lines[i - 1].is_empty();
+        let before_text = lines.get(i + 1).is_some_and(|next| !next.is_empty());
+
+        //Skips blank lines, sections, synopses and page breaks
+        if line.is_empty() || line.starts_with('#') || line.starts_with('=') {
+            i += 1;
+            continue;
+        }
+
+        if after_blank && is_scene_heading(line) {
+            let heading = line.strip_prefix('.').unwrap_or(line);
+            let heading = NUMBER_REGEX.replace(heading, "").trim().to_string();
+            scene_heading = Some(heading.clone());
+            parsed.push(ParsedLine {
+                content: heading,
+                scene_heading: scene_heading.clone(),
+                kind: LineKind::SceneHeading,
+                ..Default::default()
+            });
+            i += 1;
+            continue;
+        }
+
+        if let Some(speaker) = character_name(line).filter(|_| after_blank && before_text) {
+            //Dialogue runs until the next blank line, and a parenthetical after dialogue starts a new line for the same speaker
+            let mut current = ParsedLine { speaker: Some(speaker.clone()), scene_heading: scene_heading.clone(), ..Default::default() };
+            i += 1;
+            while i < lines.len() && !lines[i].is_empty() {
+                let dialogue = lines[i];
+                if is_parenthetical(dialogue) {
+                    if !current.content.is_empty() {
+                        let next = ParsedLine { speaker: Some(speaker.clone()), scene_heading: scene_heading.clone(), ..Default::default() };
+                        parsed.push(std::mem::replace(&mut current, next));
+                    }
+                    match &mut current.parenthetical {
+                        Some(parenthetical) => {
+                            parenthetical.push(' ');
+                            parenthetical.push_str(dialogue);
+                        }
+                        None => current.parenthetical = Some(dialogue.to_string()),
+                    }
+                } else {
+                    if !current.content.is_empty() {
+                        current.content.push(' ');
+                    }
+                    current.content.push_str(dialogue.trim_start_matches('~'));
+                }
+                i += 1;
+            }
+            if !current.content.is_empty() || current.parenthetical.is_some() {
+                parsed.push(current);
+            }
+            continue;
+        }
+
+        //Anything else is action, a transition or centered text, joined into one line per paragraph
+        let mut content = String::new();
+        while i < lines.len() && !lines[i].is_empty() {
+            let action = lines[i];
+            let action = action.strip_prefix('!').unwrap_or(action);
+            let action = action.strip_prefix('>').unwrap_or(action).trim_end_matches('<').trim();
+            if !content.is_empty() {
+                content.push(' ');
+            }
+            content.push_str(action);
+            i += 1;
+        }
+        if !content.is_empty() {
+            parsed.push(ParsedLine {
+                content,
+                scene_heading: scene_heading.clone(),
+                kind: LineKind::StageDirection,
+                ..Default::default()
+            });
+        }
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_headings_cues_parentheticals_and_action() {
+        let text = "Title: The Pilot\nCredit: Written by\n\nINT. MONK'S CAFE - DAY #1A#\n\nJerry sits. /* cut this */\n\nJERRY (V.O.)\n(beat)\nSo what's the deal? [[note]]\n\nGEORGE\nI don't know.\n";
+        let lines = parse(text);
+        let parsed: Vec<_> = lines.iter().map(|line| (line.kind, line.speaker.as_deref(), line.content.as_str())).collect();
+        assert_eq!(
+            parsed,
+            vec![
+                (LineKind::SceneHeading, None, "INT. MONK'S CAFE - DAY"),
+                (LineKind::StageDirection, None, "Jerry sits."),
+                (LineKind::Dialogue, Some("JERRY (V.O.)"), "So what's the deal?"),
+                (LineKind::Dialogue, Some("GEORGE"), "I don't know."),
+            ]
+        );
+        assert_eq!(lines[2].parenthetical.as_deref(), Some("(beat)"));
+        assert!(lines.iter().all(|line| line.scene_heading.as_deref() == Some("INT. MONK'S CAFE - DAY")));
+    }
+
+    #[test]
+    fn cues_keep_extensions_for_the_speaker_detector() {
+        let mut lines = parse("@McCLANE (drunk) (V.O.)^\nYippee.\n\nHANS #2#\nNo.\n");
+        let speakers = SpeakerDetector::new(serde_json::from_value(serde_json::json!({ "strip_suffixes": ["drunk", "v.o."] })).unwrap()).unwrap();
+        speakers.apply(&mut lines);
+        assert_eq!(lines[0].speaker.as_deref(), Some("McCLANE"));
+        assert_eq!(lines[0].speaker_label.as_deref(), Some("McCLANE (drunk) (V.O.)"));
+        assert_eq!(lines[1].speaker.as_deref(), Some("HANS"));
+
+        let mut lines = parse("JERRY (drunk)\nHello.\n");
+        SpeakerDetector::default().apply(&mut lines);
+        assert_eq!(lines[0].speaker.as_deref(), Some("JERRY (drunk)"));
+    }
+
+    #[test]
+    fn detects_screenplays_over_colon_transcripts() {
+        let speakers = SpeakerDetector::default();
+        assert!(looks_like_screenplay("JERRY\nHello.\n\nGEORGE\nHi.\n", &speakers));
+        assert!(!looks_like_screenplay("JERRY: Hello.\nGEORGE: Hi.\nELAINE: Hey.\n", &speakers));
+    }
+}
@@ -0,0 +1,180 @@
+use super::is_date;
+
+///Episode details besides the season, number and title, from a transcript's header block or the manifest
+#[derive(Debug, Default)]
+pub(super) struct EpisodeDetails {
+    pub(super) air_date: Option<String>,
+    pub(super) writers: Vec<String>,
+    pub(super) directors: Vec<String>,
+    pub(super) synopsis: Option<String>,
+    pub(super) production_code: Option<String>,
+}
+
+impl EpisodeDetails {
+    ///Fills in the details that are missing with the ones from other
+    pub(super) fn or(self, other: EpisodeDetails) -> EpisodeDetails {
+        let or_list = |list: Vec<String>, other: Vec<String>| if list.is_empty() { other } else { list };
+        EpisodeDetails {
+            air_date: self.air_date.or(other.air_date),
+            writers: or_list(self.writers, other.writers),
+            directors: or_list(self.directors, other.directors),
+            synopsis: self.synopsis.or(other.synopsis),
+            production_code: self.production_code.or(other.production_code),
+        }
+    }
+}
+
+///The header block at the top of a transcript file, and the problems with its values
+#[derive(Debug, Default)]
+pub(super) struct Header {
+    pub(super) title: Option<String>,
+    pub(super) details: EpisodeDetails,
+    pub(super) warnings: Vec<String>,
+}
+
+///The episode detail a header key sets
+enum Field {
+    Title,
+    AirDate,
+    Writers,
+    Directors,
+    Synopsis,
+    ProductionCode,
+    ///Other keys of a Fountain title page, which are allowed in the block but not kept
+    Ignored,
+}
+
+///Gets the field for a header key like "Written by", ignoring case and spacing
+fn field(key: &str) -> Option<Field> {
+    let key = key.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    match key.as_str() {
+        "title" | "episode title" => Some(Field::Title),
+        "air date" | "airdate" | "aired" | "original air date" | "first aired" => Some(Field::AirDate),
+        "written by" | "writer" | "writers" | "teleplay by" | "author" | "authors" => Some(Field::Writers),
+        "directed by" | "director" | "directors" => Some(Field::Directors),
+        "synopsis" | "summary" => Some(Field::Synopsis),
+        "production code" | "prod code" | "production number" => Some(Field::ProductionCode),
+        "credit" | "source" | "draft date" | "contact" | "copyright" | "revision" => Some(Field::Ignored),
+        _ => None,
+    }
+}
+
+///Splits a list of names like "Larry David & Jerry Seinfeld" into each name
+fn names(value: &str) -> Vec<String> {
+    value
+        .split([',', '&', ';'])
+        .flat_map(|name| name.split(" and "))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+///Splits the header block off the top of a transcript, returning the header and the rest of the text.
+///The block is a run of "Key: value" lines that ends with a blank line or "---", and can also be wrapped in "---" lines.
+///Without a known key on every line the text has no header, so a transcript that starts with "Writer: Hello" keeps its first line
+pub(super) fn split_header(text: &str) -> (Header, &str) {
+    let mut fields = Vec::new();
+    let mut fenced = false;
+    let mut end = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim();
+        if fields.is_empty() && !fenced {
+            if line.is_empty() {
+                continue;
+            }
+            if line == "---" {
+                fenced = true;
+                continue;
+            }
+        }
+        if line == "---" || (line.is_empty() && !fenced) {
+            end = Some(offset);
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        match line.split_once(':').and_then(|(key, value)| Some((field(key)?, value.trim()))) {
+            Some(field) => fields.push(field),
+            None => return (Header::default(), text),
+        }
+    }
+    let Some(end) = end.filter(|_| !fields.is_empty()) else {
+        return (Header::default(), text);
+    };
+
+    let mut header = Header::default();
+    for (field, value) in fields {
+        if value.is_empty() {
+            continue;
+        }
+        match field {
+            Field::Title => header.title = Some(value.to_string()),
+            Field::AirDate if is_date(value) => header.details.air_date = Some(value.to_string()),
+            Field::AirDate => header.warnings.push(format!("Air date {} in the header is not formatted as YYYY-MM-DD, so it was left out", value)),
+            Field::Writers => header.details.writers.extend(names(value)),
+            Field::Directors => header.details.directors.extend(names(value)),
+            Field::Synopsis => header.details.synopsis = Some(value.to_string()),
+            Field::ProductionCode => header.details.production_code = Some(value.to_string()),
+            Field::Ignored => {}
+        }
+    }
+    (header, &text[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_header_block_off() {
+        let text = "Title: The Stake Out\nWritten by: Larry David & Jerry Seinfeld\nDirected by: Tom Cherones\nAir date: 1990-05-31\nProd code: 102\nSynopsis: Jerry wants to meet a woman.\n\nJERRY: So, what do you think?\n";
+        let (header, rest) = split_header(text);
+        assert_eq!(rest, "JERRY: So, what do you think?\n");
+        assert_eq!(header.title.as_deref(), Some("The Stake Out"));
+        assert_eq!(header.details.writers, vec!["Larry David", "Jerry Seinfeld"]);
+        assert_eq!(header.details.directors, vec!["Tom Cherones"]);
+        assert_eq!(header.details.air_date.as_deref(), Some("1990-05-31"));
+        assert_eq!(header.details.production_code.as_deref(), Some("102"));
+        assert_eq!(header.details.synopsis.as_deref(), Some("Jerry wants to meet a woman."));
+        assert!(header.warnings.is_empty());
+    }
+
+    #[test]
+    fn reads_a_fenced_header() {
+        let (header, rest) = split_header("---\nTitle: The Pilot\n\nAuthors: Larry David and Jerry Seinfeld\n---\nJERRY: Hi.\n");
+        assert_eq!(rest, "JERRY: Hi.\n");
+        assert_eq!(header.title.as_deref(), Some("The Pilot"));
+        assert_eq!(header.details.writers, vec!["Larry David", "Jerry Seinfeld"]);
+    }
+
+    #[test]
+    fn keeps_text_without_a_header() {
+        let text = "Writer: Hello\nJERRY: Hi.\n\nGEORGE: Hey.\n";
+        let (header, rest) = split_header(text);
+        assert_eq!(rest, text);
+        assert!(header.title.is_none());
+
+        let text = "JERRY: Hi.\n";
+        assert_eq!(split_header(text).1, text);
+    }
+
+    #[test]
+    fn warns_about_a_malformed_air_date() {
+        let (header, _) = split_header("Aired: May 31, 1990\n\nJERRY: Hi.\n");
+        assert!(header.details.air_date.is_none());
+        assert_eq!(header.warnings.len(), 1);
+    }
+
+    #[test]
+    fn or_keeps_the_first_details() {
+        let first = EpisodeDetails { synopsis: Some("First".to_string()), ..Default::default() };
+        let second = EpisodeDetails { synopsis: Some("Second".to_string()), writers: vec!["Larry David".to_string()], ..Default::default() };
+        let details = first.or(second);
+        assert_eq!(details.synopsis.as_deref(), Some("First"));
+        assert_eq!(details.writers, vec!["Larry David"]);
+    }
+}
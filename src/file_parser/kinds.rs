@@ -0,0 +1,126 @@
+use super::fountain::is_scene_heading;
+use super::ParsedLine;
+use crate::models::LineKind;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    //Checked for every line of every upload, so these are only compiled once
+    static ref INLINE_ACTION_REGEX: Regex = Regex::new(r"\[[^\]]*\]|\([^)]*\)").unwrap();
+    //"Scene:" style labels, shared with scene detection so line kinds and scene boundaries agree. The match ends after the label's spaces
+    pub(super) static ref SCENE_LABEL_REGEX: Regex = Regex::new(r"(?i)^(?:scene|setting|location)\s*:\s*").unwrap();
+}
+
+///Gets the text inside brackets or parentheses, if they wrap the whole line
+pub(super) fn bracketed(content: &str) -> Option<&str> {
+    content
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .or_else(|| content.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')))
+        .filter(|inner| !inner.contains([']', ')']))
+        .map(str::trim)
+}
+
+///Sets the kind of every line a parser left as dialogue, and moves inline actions like "(laughs)" out of dialogue into the parenthetical
+pub fn classify(lines: &mut [ParsedLine]) {
+    for line in lines.iter_mut().filter(|line| line.kind == LineKind::Dialogue) {
+        let content = line.content.trim();
+        if content.is_empty() {
+            line.kind = if line.parenthetical.is_some() { LineKind::InlineAction } else { LineKind::Blank };
+            continue;
+        }
+        if let Some(inner) = bracketed(content) {
+            line.kind = if SCENE_LABEL_REGEX.is_match(inner) || is_scene_heading(inner) {
+                LineKind::SceneHeading
+            } else if line.speaker.is_some() {
+                LineKind::InlineAction
+            } else {
+                LineKind::StageDirection
+            };
+            continue;
+        }
+        if line.speaker.is_none() && is_scene_heading(content) {
+            line.kind = LineKind::SceneHeading;
+            continue;
+        }
+
+        //Dialogue with its inline actions taken out, a line that is only actions keeps them as its content
+        let actions: Vec<&str> = INLINE_ACTION_REGEX.find_iter(content).map(|action| action.as_str()).collect();
+        if actions.is_empty() {
+            continue;
+        }
+        let dialogue = INLINE_ACTION_REGEX.replace_all(content, " ").split_whitespace().collect::<Vec<_>>().join(" ");
+        if dialogue.is_empty() {
+            line.kind = if line.speaker.is_some() { LineKind::InlineAction } else { LineKind::StageDirection };
+            continue;
+        }
+        let actions = actions.join(" ");
+        line.parenthetical = Some(match line.parenthetical.take() {
+            Some(parenthetical) => format!("{} {}", parenthetical, actions),
+            None => actions,
+        });
+        line.content = dialogue;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classified(lines: &[(Option<&str>, &str)]) -> Vec<ParsedLine> {
+        let mut lines: Vec<ParsedLine> = lines
+            .iter()
+            .map(|(speaker, content)| ParsedLine { speaker: speaker.map(str::to_string), content: content.to_string(), ..Default::default() })
+            .collect();
+        classify(&mut lines);
+        lines
+    }
+
+    #[test]
+    fn classifies_each_kind() {
+        let lines = classified(&[
+            (None, "[Scene: Monk's Cafe]"),
+            (None, "INT. JERRY'S APARTMENT - DAY"),
+            (None, "(Door slams)"),
+            (Some("GEORGE"), "(sighs)"),
+            (Some("JERRY"), "So what's the deal?"),
+            (None, "  "),
+        ]);
+        let kinds: Vec<LineKind> = lines.iter().map(|line| line.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LineKind::SceneHeading,
+                LineKind::SceneHeading,
+                LineKind::StageDirection,
+                LineKind::InlineAction,
+                LineKind::Dialogue,
+                LineKind::Blank,
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_inline_actions_into_the_parenthetical() {
+        let lines = classified(&[(Some("JERRY"), "(laughs) So what's [points] the deal?")]);
+        assert_eq!(lines[0].kind, LineKind::Dialogue);
+        assert_eq!(lines[0].content, "So what's the deal?");
+        assert_eq!(lines[0].parenthetical.as_deref(), Some("(laughs) [points]"));
+    }
+
+    #[test]
+    fn leaves_kinds_set_by_the_parser() {
+        let mut lines = vec![ParsedLine { content: "(beat)".to_string(), kind: LineKind::StageDirection, ..Default::default() }];
+        classify(&mut lines);
+        assert_eq!(lines[0].kind, LineKind::StageDirection);
+        assert_eq!(lines[0].parenthetical, None);
+    }
+
+    #[test]
+    fn bracketed_only_matches_whole_lines() {
+        assert_eq!(bracketed("[ Scene: Monk's ]"), Some("Scene: Monk's"));
+        assert_eq!(bracketed("(laughs)"), Some("laughs"));
+        assert_eq!(bracketed("(laughs) and (cries)"), None);
+        assert_eq!(bracketed("Hello"), None);
+    }
+}
@@ -0,0 +1,50 @@
+use super::is_date;
+use crate::models::{Manifest, ManifestEntry};
+use std::collections::HashMap;
+
+///File names a manifest can have at the archive root
+pub const MANIFEST_FILES: &[&str] = &["manifest.json", "manifest.toml"];
+
+///Normalizes a path from the manifest or the archive so they can be compared
+pub fn normalize_path(path: &str) -> String {
+    path.trim().replace('\\', "/").trim_start_matches("./").trim_start_matches('/').to_string()
+}
+
+///Parses and validates a manifest, returning its entries keyed by their normalized file path
+pub fn parse(file_name: &str, text: &str) -> Result<HashMap<String, ManifestEntry>, Vec<String>> {
+    let manifest: Manifest = if file_name.ends_with(".toml") {
+        toml::from_str(text).map_err(|err| vec![format!("{}: {}", file_name, err)])?
+    } else {
+        serde_json::from_str(text).map_err(|err| vec![format!("{}: {}", file_name, err)])?
+    };
+
+    let mut entries = HashMap::new();
+    let mut errors = Vec::new();
+    for (i, entry) in manifest.episodes.into_iter().enumerate() {
+        let location = format!("{} episodes[{}]", file_name, i);
+        let path = normalize_path(&entry.file);
+        if path.is_empty() {
+            errors.push(format!("{}: file must not be empty", location));
+        }
+        if entry.season < 0 || entry.episode < 0 || entry.absolute_number.is_some_and(|number| number < 0) {
+            errors.push(format!("{}: season, episode and absolute_number must not be negative", location));
+        }
+        if entry.episode_end.is_some_and(|end| end < entry.episode) {
+            errors.push(format!("{}: episode_end must not be before episode", location));
+        }
+        if entry.air_date.as_deref().is_some_and(|date| !is_date(date)) {
+            errors.push(format!("{}: air_date must be formatted as YYYY-MM-DD", location));
+        }
+        if entries.contains_key(&path) {
+            errors.push(format!("{}: {} is listed more than once", location, entry.file));
+            continue;
+        }
+        entries.insert(path, entry);
+    }
+
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
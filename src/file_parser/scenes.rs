@@ -0,0 +1,143 @@
+use super::kinds::{bracketed, SCENE_LABEL_REGEX};
+use super::ParsedLine;
+use crate::models::LineKind;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref INT_EXT_REGEX: Regex = Regex::new(r"(?i)^(?:int\.?\s*/\s*ext\.?|ext\.?\s*/\s*int\.?|i\s*/\s*e\.?|int\.|ext\.|est\.)\s*").unwrap();
+}
+
+///A scene found in an episode's lines, with the numbers of its first and last lines
+pub(super) struct ParsedScene {
+    pub(super) heading: Option<String>,
+    pub(super) location: Option<String>,
+    pub(super) first_line_number: i32,
+    pub(super) last_line_number: i32,
+}
+
+///Gets the place a scene heading names, "Monk's Cafe" from "[Scene: Monk's Cafe, Jerry and George are talking]"
+///or "JERRY'S APARTMENT" from "INT. JERRY'S APARTMENT - DAY"
+fn location(heading: &str) -> Option<String> {
+    let location = if let Some(label) = SCENE_LABEL_REGEX.find(heading) {
+        heading[label.end()..].split(',').next().unwrap_or_default()
+    } else if let Some(prefix) = INT_EXT_REGEX.find(heading) {
+        heading[prefix.end()..].split(" - ").next().unwrap_or_default()
+    } else {
+        heading
+    };
+    let location = location.trim().trim_end_matches('.').trim();
+    (!location.is_empty()).then(|| location.to_string())
+}
+
+///Splits an episode's lines into scenes, and gives each line without a scene heading the heading of its scene.
+///A scene starts at each scene heading, or if the file has none, after a run of blank lines.
+///Files with a blank line after most lines only break on two or more blank lines in a row
+pub(super) fn detect(lines: &mut [ParsedLine]) -> Vec<ParsedScene> {
+    let has_headings = lines.iter().any(|line| line.kind == LineKind::SceneHeading);
+    let text_lines = lines.iter().filter(|line| line.kind != LineKind::Blank).count();
+    let blank_runs = lines
+        .windows(2)
+        .filter(|pair| pair[0].kind != LineKind::Blank && pair[1].kind == LineKind::Blank)
+        .count();
+    let min_blank_run = if blank_runs * 2 > text_lines { 2 } else { 1 };
+
+    let mut scenes: Vec<ParsedScene> = Vec::new();
+    let mut blank_run = 0;
+    let mut in_scene = false;
+    for (i, line) in lines.iter_mut().enumerate() {
+        if line.kind == LineKind::Blank {
+            blank_run += 1;
+            continue;
+        }
+        let line_number = line.line_number.unwrap_or(i as i32 + 1);
+        let is_heading = line.kind == LineKind::SceneHeading;
+        if !in_scene || is_heading || (!has_headings && blank_run >= min_blank_run) {
+            let heading = is_heading.then(|| bracketed(line.content.trim()).unwrap_or(line.content.trim()).to_string());
+            scenes.push(ParsedScene {
+                location: heading.as_deref().and_then(location),
+                heading,
+                first_line_number: line_number,
+                last_line_number: line_number,
+            });
+            in_scene = true;
+        }
+        blank_run = 0;
+
+        let Some(scene) = scenes.last_mut() else {
+            continue;
+        };
+        scene.first_line_number = scene.first_line_number.min(line_number);
+        scene.last_line_number = scene.last_line_number.max(line_number);
+        if line.scene_heading.is_none() {
+            line.scene_heading = scene.heading.clone();
+        }
+    }
+    scenes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(kind: LineKind, content: &str) -> ParsedLine {
+        ParsedLine { kind, content: content.to_string(), ..Default::default() }
+    }
+
+    fn ranges(scenes: &[ParsedScene]) -> Vec<(i32, i32)> {
+        scenes.iter().map(|scene| (scene.first_line_number, scene.last_line_number)).collect()
+    }
+
+    #[test]
+    fn starts_a_scene_at_each_heading() {
+        let mut lines = vec![
+            line(LineKind::Dialogue, "Cold open."),
+            line(LineKind::SceneHeading, "[Scene: Monk's Cafe, Jerry and George are talking]"),
+            line(LineKind::Dialogue, "Hello."),
+            line(LineKind::Blank, ""),
+            line(LineKind::Dialogue, "Still the cafe."),
+            line(LineKind::SceneHeading, "INT. JERRY'S APARTMENT - DAY"),
+            line(LineKind::Dialogue, "Hi."),
+        ];
+        let scenes = detect(&mut lines);
+        assert_eq!(ranges(&scenes), vec![(1, 1), (2, 5), (6, 7)]);
+        assert_eq!(scenes[0].heading, None);
+        assert_eq!(scenes[1].heading.as_deref(), Some("Scene: Monk's Cafe, Jerry and George are talking"));
+        assert_eq!(scenes[1].location.as_deref(), Some("Monk's Cafe"));
+        assert_eq!(scenes[2].location.as_deref(), Some("JERRY'S APARTMENT"));
+        assert_eq!(lines[4].scene_heading, scenes[1].heading);
+    }
+
+    #[test]
+    fn breaks_on_blank_lines_without_headings() {
+        let mut lines = vec![
+            line(LineKind::Dialogue, "One."),
+            line(LineKind::Dialogue, "Two."),
+            line(LineKind::Blank, ""),
+            line(LineKind::Dialogue, "Three."),
+        ];
+        assert_eq!(ranges(&detect(&mut lines)), vec![(1, 2), (4, 4)]);
+    }
+
+    #[test]
+    fn needs_two_blank_lines_when_most_lines_have_one_after_them() {
+        let mut lines = vec![
+            line(LineKind::Dialogue, "One."),
+            line(LineKind::Blank, ""),
+            line(LineKind::Dialogue, "Two."),
+            line(LineKind::Blank, ""),
+            line(LineKind::Blank, ""),
+            line(LineKind::Dialogue, "Three."),
+            line(LineKind::Blank, ""),
+        ];
+        assert_eq!(ranges(&detect(&mut lines)), vec![(1, 3), (6, 6)]);
+    }
+
+    #[test]
+    fn location_reads_labels_and_sluglines() {
+        assert_eq!(location("Setting: Central Perk."), Some("Central Perk".to_string()));
+        assert_eq!(location("EXT. STREET - NIGHT"), Some("STREET".to_string()));
+        assert_eq!(location("INT./EXT. CAR - MOVING"), Some("CAR".to_string()));
+        assert_eq!(location("Scene:"), None);
+    }
+}
@@ -0,0 +1,194 @@
+use super::ParsedLine;
+use crate::models::{SpeakerCase, SpeakerRules};
+use regex::Regex;
+
+///Speaker detection rules from an upload, with the pattern compiled and the lists lowercased for matching
+#[derive(Debug)]
+pub struct SpeakerDetector {
+    max_length: usize,
+    pattern: Option<Regex>,
+    case: SpeakerCase,
+    strip_suffixes: Vec<String>,
+    ignore: Vec<String>,
+    separators: Vec<String>,
+}
+
+impl Default for SpeakerDetector {
+    fn default() -> Self {
+        SpeakerDetector::new(SpeakerRules::default()).unwrap()
+    }
+}
+
+impl SpeakerDetector {
+    ///Compiles the rules, failing if the pattern isn't a valid regex
+    pub fn new(rules: SpeakerRules) -> Result<Self, regex::Error> {
+        //The pattern has to match the whole label, not just part of it
+        let pattern = rules.pattern.map(|pattern| Regex::new(&format!("^(?:{})$", pattern))).transpose()?;
+        let lowercase = |values: Vec<String>| values.into_iter().map(|value| value.trim().to_lowercase()).collect();
+        Ok(SpeakerDetector {
+            max_length: rules.max_length,
+            pattern,
+            case: rules.case,
+            strip_suffixes: lowercase(rules.strip_suffixes),
+            ignore: lowercase(rules.ignore),
+            separators: rules.separators.into_iter().filter(|separator| !separator.is_empty()).collect(),
+        })
+    }
+
+    ///Checks if the text before a colon can be a speaker label.
+    ///It needs a letter, which rules out times like "10:30", it can't open a bracket like "[Scene: Monk's Cafe]",
+    ///and the text after the colon can't start with "//", which rules out URLs
+    pub(super) fn is_speaker(&self, label: &str, content: &str) -> bool {
+        !label.is_empty()
+            && label.chars().count() <= self.max_length
+            && label.chars().any(char::is_alphabetic)
+            && !label.starts_with(['[', '('])
+            && !content.starts_with("//")
+            && !self.ignore.contains(&label.to_lowercase())
+            && self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(label))
+    }
+
+    ///Drops extensions like (V.O.) or (CONT'D) from the end of a speaker label, then applies the case normalization
+    pub(super) fn normalize(&self, label: &str) -> String {
+        let mut name = label.trim();
+        while let Some(rest) = name.strip_suffix(')') {
+            let Some((before, suffix)) = rest.rsplit_once('(') else {
+                break;
+            };
+            if !self.strip_suffixes.contains(&suffix.trim().to_lowercase()) {
+                break;
+            }
+            name = before.trim_end();
+        }
+        //A label that is nothing but an extension is kept as it is
+        if name.is_empty() {
+            name = label.trim();
+        }
+        match self.case {
+            SpeakerCase::Preserve => name.to_string(),
+            SpeakerCase::Upper => name.to_uppercase(),
+            SpeakerCase::Lower => name.to_lowercase(),
+            SpeakerCase::Title => title_case(name),
+        }
+    }
+
+    ///Splits a combined label like "Ross & Rachel" on the separators, keeping the whole label if nothing is left
+    fn split<'a>(&self, label: &'a str) -> Vec<&'a str> {
+        let mut names = vec![label];
+        for separator in &self.separators {
+            names = names.into_iter().flat_map(|name| name.split(separator.as_str())).collect();
+        }
+        let names: Vec<&str> = names.into_iter().map(str::trim).filter(|name| !name.is_empty()).collect();
+        if names.is_empty() {
+            vec![label.trim()]
+        } else {
+            names
+        }
+    }
+
+    ///Keeps each line's speaker as its raw label, and splits and normalizes it into the line's speakers
+    pub(super) fn apply(&self, lines: &mut [ParsedLine]) {
+        for line in lines {
+            if let Some(label) = line.speaker.take() {
+                line.speakers = Vec::new();
+                for name in self.split(&label) {
+                    let name = self.normalize(name);
+                    if !line.speakers.contains(&name) {
+                        line.speakers.push(name);
+                    }
+                }
+                line.speaker = line.speakers.first().cloned();
+                line.speaker_label = Some(label);
+            }
+        }
+    }
+
+    ///Splits the speakers of lines from structured files, which give their speaker names as they should be stored so they aren't normalized
+    pub(super) fn split_speakers(&self, lines: &mut [ParsedLine]) {
+        for line in lines {
+            if let Some(speaker) = &line.speaker {
+                line.speakers = Vec::new();
+                for name in self.split(speaker) {
+                    if !line.speakers.iter().any(|speaker| speaker == name) {
+                        line.speakers.push(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+///Capitalizes the first letter of each word and lowercases the rest, so "JERRY" and "jerry" both become "Jerry"
+fn title_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut word_start = true;
+    for c in name.chars() {
+        if word_start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace() || c == '-' || c == '.';
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(rules: serde_json::Value) -> SpeakerDetector {
+        SpeakerDetector::new(serde_json::from_value(rules).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn is_speaker_rejects_times_urls_brackets_and_ignored_labels() {
+        let speakers = SpeakerDetector::default();
+        assert!(speakers.is_speaker("JERRY", "Hello"));
+        assert!(!speakers.is_speaker("10", "30 PM"));
+        assert!(!speakers.is_speaker("https", "//example.com"));
+        assert!(!speakers.is_speaker("Visit http", "//example.com"));
+        assert!(!speakers.is_speaker("[Scene", "Monk's Cafe]"));
+        assert!(!speakers.is_speaker("Note", "bring milk"));
+        assert!(!speakers.is_speaker(&"A".repeat(41), "Hello"));
+    }
+
+    #[test]
+    fn is_speaker_applies_the_pattern_and_max_length() {
+        let speakers = detector(serde_json::json!({ "pattern": "[A-Z][A-Z .']+", "max_length": 10 }));
+        assert!(speakers.is_speaker("MR. PITT", "Hello"));
+        assert!(!speakers.is_speaker("Mr. Pitt", "Hello"));
+        assert!(!speakers.is_speaker("MR. PITT SR.", "Hello"));
+    }
+
+    #[test]
+    fn new_rejects_an_invalid_pattern() {
+        assert!(SpeakerDetector::new(serde_json::from_value(serde_json::json!({ "pattern": "(" })).unwrap()).is_err());
+    }
+
+    #[test]
+    fn normalize_strips_extensions_and_applies_case() {
+        let speakers = SpeakerDetector::default();
+        assert_eq!(speakers.normalize("JERRY (V.O.) (CONT'D)"), "JERRY");
+        assert_eq!(speakers.normalize("JERRY (drunk)"), "JERRY (drunk)");
+        assert_eq!(speakers.normalize("(V.O.)"), "(V.O.)");
+        assert_eq!(detector(serde_json::json!({ "case": "title" })).normalize("MR. COSTANZA"), "Mr. Costanza");
+        assert_eq!(detector(serde_json::json!({ "case": "upper" })).normalize("Kramer"), "KRAMER");
+    }
+
+    #[test]
+    fn apply_splits_combined_labels_and_keeps_the_raw_label() {
+        let mut lines = vec![ParsedLine { speaker: Some("Ross & Rachel & ROSS (O.S.)".to_string()), ..Default::default() }];
+        SpeakerDetector::default().apply(&mut lines);
+        assert_eq!(lines[0].speakers, vec!["Ross", "Rachel", "ROSS"]);
+        assert_eq!(lines[0].speaker.as_deref(), Some("Ross"));
+        assert_eq!(lines[0].speaker_label.as_deref(), Some("Ross & Rachel & ROSS (O.S.)"));
+    }
+
+    #[test]
+    fn split_speakers_does_not_normalize() {
+        let mut lines = vec![ParsedLine { speaker: Some("JERRY (V.O.)/Elaine".to_string()), ..Default::default() }];
+        SpeakerDetector::default().split_speakers(&mut lines);
+        assert_eq!(lines[0].speakers, vec!["JERRY (V.O.)", "Elaine"]);
+    }
+}
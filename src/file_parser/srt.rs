@@ -0,0 +1,46 @@
+use super::{parse_cue_text, parse_timecode, split_blocks, ParsedLine, SpeakerDetector};
+
+///Parses an SRT subtitle file into lines, keeping each cue's start and end times
+pub fn parse(text: &str, speakers: &SpeakerDetector) -> Vec<ParsedLine> {
+    let mut lines = Vec::new();
+
+    //Each cue is an index line, a timing line, then the text lines
+    for block in split_blocks(text) {
+        let Some(timing_index) = block.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        let Some((start, end)) = block[timing_index].split_once("-->") else {
+            continue;
+        };
+        let template = ParsedLine {
+            start_ms: parse_timecode(start),
+            end_ms: end.split_whitespace().next().and_then(parse_timecode),
+            ..Default::default()
+        };
+        lines.extend(parse_cue_text(&block[timing_index + 1..], &template, speakers));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cues_with_timings_and_speakers() {
+        let text = "1\n00:00:01,000 --> 00:00:02,500\nJERRY: What's the deal\nwith airline food?\n\n2\n00:00:03,000 --> 00:00:04,000 X1:0\n<i>GEORGE: No idea.</i>\nELAINE: Me neither.\n";
+        let lines = parse(text, &SpeakerDetector::default());
+        let parsed: Vec<_> = lines
+            .iter()
+            .map(|line| (line.speaker.as_deref(), line.content.as_str(), line.start_ms, line.end_ms))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                (Some("JERRY"), "What's the deal with airline food?", Some(1000), Some(2500)),
+                (Some("GEORGE"), "No idea.", Some(3000), Some(4000)),
+                (Some("ELAINE"), "Me neither.", Some(3000), Some(4000)),
+            ]
+        );
+    }
+}
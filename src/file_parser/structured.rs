@@ -0,0 +1,146 @@
+use super::ParsedLine;
+use crate::models::{TranscriptUpload, TranscriptUploadLine};
+use serde_json::Value;
+
+///An episode read from a structured transcript file, which carries its own season + episode numbers
+pub struct StructuredEpisode {
+    pub season: i32,
+    pub episode: i32,
+    pub title: String,
+    pub lines: Vec<ParsedLine>,
+}
+
+///Checks the values serde can't, returning one message per bad row
+fn validate(upload: &TranscriptUpload, location: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    if upload.season < 0 {
+        errors.push(format!("{}: season must not be negative", location));
+    }
+    if upload.episode < 0 {
+        errors.push(format!("{}: episode must not be negative", location));
+    }
+    if upload.lines.is_empty() {
+        errors.push(format!("{}: lines must not be empty", location));
+    }
+    for (i, line) in upload.lines.iter().enumerate() {
+        let row = format!("{} lines[{}]", location, i);
+        if line.content.trim().is_empty() {
+            errors.push(format!("{}: content must not be empty", row));
+        }
+        if line.speaker.as_deref().is_some_and(|speaker| speaker.trim().is_empty()) {
+            errors.push(format!("{}: speaker must not be empty, leave it out or use null instead", row));
+        }
+        if line.start_ms.is_some_and(|ms| ms < 0) || line.end_ms.is_some_and(|ms| ms < 0) {
+            errors.push(format!("{}: timestamps must not be negative", row));
+        }
+        if let (Some(start), Some(end)) = (line.start_ms, line.end_ms) {
+            if end < start {
+                errors.push(format!("{}: end_ms must not be before start_ms", row));
+            }
+        }
+    }
+    errors
+}
+
+///Deserializes one episode object, reporting bad lines individually so errors point at the offending row
+fn parse_episode(
+    value: Value,
+    location: &str,
+    show: &mut Option<String>,
+    errors: &mut Vec<String>,
+) -> Option<StructuredEpisode> {
+    let line_values = value.get("lines").and_then(Value::as_array).cloned().unwrap_or_default();
+    let upload: TranscriptUpload = match serde_json::from_value(value) {
+        Ok(upload) => upload,
+        Err(err) => {
+            let line_errors: Vec<String> = line_values
+                .into_iter()
+                .enumerate()
+                .filter_map(|(i, line)| {
+                    serde_json::from_value::<TranscriptUploadLine>(line)
+                        .err()
+                        .map(|err| format!("{} lines[{}]: {}", location, i, err))
+                })
+                .collect();
+            if line_errors.is_empty() {
+                errors.push(format!("{}: {}", location, err));
+            } else {
+                errors.extend(line_errors);
+            }
+            return None;
+        }
+    };
+
+    let mut validation_errors = validate(&upload, location);
+
+    //A dataset holds a single show, so every episode in a file has to agree on it
+    if let Some(upload_show) = &upload.show {
+        match show {
+            Some(expected) if expected != upload_show => validation_errors.push(format!(
+                "{}: show \"{}\" does not match \"{}\" used earlier in the file",
+                location, upload_show, expected
+            )),
+            Some(_) => {}
+            None => *show = Some(upload_show.clone()),
+        }
+    }
+    if !validation_errors.is_empty() {
+        errors.extend(validation_errors);
+        return None;
+    }
+
+    let lines = upload
+        .lines
+        .into_iter()
+        .map(|line| ParsedLine {
+            speaker: line.speaker.map(|speaker| speaker.trim().to_string()),
+            content: line.content.trim().to_string(),
+            start_ms: line.start_ms,
+            end_ms: line.end_ms,
+            ..Default::default()
+        })
+        .collect();
+    Some(StructuredEpisode {
+        season: upload.season,
+        episode: upload.episode,
+        title: upload.title.trim().to_string(),
+        lines,
+    })
+}
+
+///Parses a JSON file (one episode object or an array of them) or a JSONL file (one episode object per line)
+pub fn parse(file_name: &str, text: &str, jsonl: bool) -> Result<Vec<StructuredEpisode>, Vec<String>> {
+    let mut episodes = Vec::new();
+    let mut errors = Vec::new();
+    let mut show = None;
+
+    if jsonl {
+        for (i, row) in text.lines().enumerate() {
+            if row.trim().is_empty() {
+                continue;
+            }
+            let location = format!("{} line {}", file_name, i + 1);
+            match serde_json::from_str::<Value>(row) {
+                Ok(value) => episodes.extend(parse_episode(value, &location, &mut show, &mut errors)),
+                Err(err) => errors.push(format!("{}: invalid JSON: {}", location, err)),
+            }
+        }
+    } else {
+        match serde_json::from_str::<Value>(text) {
+            Ok(Value::Array(values)) => {
+                for (i, value) in values.into_iter().enumerate() {
+                    let location = format!("{} [{}]", file_name, i);
+                    episodes.extend(parse_episode(value, &location, &mut show, &mut errors));
+                }
+            }
+            Ok(value) => episodes.extend(parse_episode(value, file_name, &mut show, &mut errors)),
+            Err(err) => errors.push(format!("{}: invalid JSON: {}", file_name, err)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(episodes)
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,119 @@
+use super::{parse_cue_text, parse_timecode, split_blocks, ParsedLine, SpeakerDetector};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref TAG_REGEX: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref VOICE_REGEX: Regex = Regex::new(r"<v(?:\.[^\s>]*)?(?:\s+([^>]*))?>").unwrap();
+}
+
+///Replaces the character references WebVTT allows in cue text
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "")
+        .replace("&rlm;", "")
+        .replace("&amp;", "&")
+}
+
+///Splits cue text on its <v Name> voice spans, using the voice name as the speaker of each span
+fn parse_voice_spans(text: &str, template: &ParsedLine, speakers: &SpeakerDetector) -> Vec<ParsedLine> {
+    let mut lines = Vec::new();
+
+    //Text before the first voice span falls back to the usual "Speaker: content" heuristic
+    let first_voice = VOICE_REGEX.find(text).map_or(text.len(), |m| m.start());
+    let leading: Vec<&str> = text[..first_voice].lines().collect();
+    lines.extend(parse_cue_text(&leading, template, speakers));
+
+    let voices: Vec<_> = VOICE_REGEX.captures_iter(text).collect();
+    for (i, caps) in voices.iter().enumerate() {
+        let span_start = caps.get(0).map_or(0, |m| m.end());
+        let span_end = voices.get(i + 1).and_then(|next| next.get(0)).map_or(text.len(), |m| m.start());
+        let content = TAG_REGEX.replace_all(&text[span_start..span_end], "");
+        let content = decode_entities(&content.split_whitespace().collect::<Vec<_>>().join(" "));
+        if content.is_empty() {
+            continue;
+        }
+        let speaker = caps
+            .get(1)
+            .map(|name| decode_entities(name.as_str().trim()))
+            .filter(|name| !name.is_empty());
+        lines.push(ParsedLine {
+            speaker,
+            content,
+            ..template.clone()
+        });
+    }
+    lines
+}
+
+///Parses a WebVTT caption file into lines, keeping cue timings, cue settings and <v> voice speakers
+pub fn parse(text: &str, speakers: &SpeakerDetector) -> Vec<ParsedLine> {
+    let mut lines = Vec::new();
+
+    for block in split_blocks(text) {
+        //Skips the file header and NOTE, STYLE and REGION blocks
+        let first = block[0].trim_start();
+        if ["WEBVTT", "NOTE", "STYLE", "REGION"].iter().any(|keyword| first.starts_with(keyword)) {
+            continue;
+        }
+
+        //A cue is an optional identifier line, a timing line with optional settings, then the text lines
+        let Some(timing_index) = block.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        let Some((start, rest)) = block[timing_index].split_once("-->") else {
+            continue;
+        };
+        let mut rest = rest.split_whitespace();
+        let end = rest.next();
+        let settings = rest.collect::<Vec<_>>().join(" ");
+        let template = ParsedLine {
+            start_ms: parse_timecode(start),
+            end_ms: end.and_then(parse_timecode),
+            cue_settings: Some(settings).filter(|settings| !settings.is_empty()),
+            ..Default::default()
+        };
+
+        let text_lines = &block[timing_index + 1..];
+        let cue_text = text_lines.join("\n");
+        if VOICE_REGEX.is_match(&cue_text) {
+            lines.extend(parse_voice_spans(&cue_text, &template, speakers));
+        } else {
+            //Tags are stripped before the entities are decoded, so escaped text like &lt;b&gt; is kept
+            let mut cue = parse_cue_text(text_lines, &template, speakers);
+            for line in &mut cue {
+                line.content = decode_entities(&line.content);
+                line.speaker = line.speaker.as_deref().map(decode_entities);
+            }
+            lines.extend(cue);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_escaped_tags_as_text() {
+        let lines = parse("WEBVTT\n\n00:01.000 --> 00:02.000\nJERRY: <i>Use</i> &lt;b&gt; &amp; &lt;i&gt;\n", &SpeakerDetector::default());
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].speaker.as_deref(), Some("JERRY"));
+        assert_eq!(lines[0].content, "Use <b> & <i>");
+        assert_eq!((lines[0].start_ms, lines[0].end_ms), (Some(1000), Some(2000)));
+    }
+
+    #[test]
+    fn uses_voice_spans_as_speakers() {
+        let lines = parse(
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:03.500 align:start\n<v Jerry>Hello.</v>\n<v.loud Elaine>Get &lt;out&gt;!</v>\n",
+            &SpeakerDetector::default(),
+        );
+        let parsed: Vec<_> = lines.iter().map(|line| (line.speaker.as_deref(), line.content.as_str())).collect();
+        assert_eq!(parsed, vec![(Some("Jerry"), "Hello."), (Some("Elaine"), "Get <out>!")]);
+        assert_eq!(lines[0].cue_settings.as_deref(), Some("align:start"));
+    }
+}
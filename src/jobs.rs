@@ -0,0 +1,195 @@
+use crate::models::{IngestReport, JobState, JobStatus, ProgressEvent};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex};
+use uuid::Uuid;
+
+pub type JobRegistry = Arc<Mutex<HashMap<String, Arc<Job>>>>;
+
+///How many edits each user has in progress outside of an upload job, like replacing an episode or renaming a speaker.
+///It is only locked while the job registry is locked, or on its own to release an edit
+pub type EditRegistry = Arc<std::sync::Mutex<HashMap<String, usize>>>;
+
+///A user's reservation for an edit, no upload can start for the user until it is dropped.
+///Releasing it on drop frees the reservation even if the edit fails or the request is dropped part way
+pub struct EditGuard {
+    edits: EditRegistry,
+    user_id: String,
+}
+
+impl EditGuard {
+    ///Reserves an edit for the user, the caller holds the job registry's lock and has checked the user has no active upload
+    pub fn new(edits: &EditRegistry, user_id: &str) -> Self {
+        *edits.lock().unwrap().entry(user_id.to_string()).or_default() += 1;
+        EditGuard { edits: Arc::clone(edits), user_id: user_id.to_string() }
+    }
+}
+
+impl Drop for EditGuard {
+    fn drop(&mut self) {
+        let mut edits = self.edits.lock().unwrap();
+        if let Some(count) = edits.get_mut(&self.user_id) {
+            *count -= 1;
+            if *count == 0 {
+                edits.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+///How long a finished job is kept for its status to be polled, its report stays in the user's database after that
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+///Most finished jobs kept at once, the oldest are removed first
+const MAX_FINISHED_JOBS: usize = 100;
+
+///Removes finished jobs that are past the TTL, and the oldest finished jobs past the cap, so the registry doesn't keep every report forever
+pub fn prune_finished(jobs: &mut HashMap<String, Arc<Job>>) {
+    jobs.retain(|_, job| job.finished_at().is_none_or(|finished_at| finished_at.elapsed() < FINISHED_JOB_TTL));
+    let mut finished: Vec<(Instant, String)> =
+        jobs.iter().filter_map(|(id, job)| job.finished_at().map(|finished_at| (finished_at, id.clone()))).collect();
+    if finished.len() > MAX_FINISHED_JOBS {
+        finished.sort();
+        for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(id);
+        }
+    }
+}
+
+///Error returned by ingestion when its job was cancelled, the caller rolls back the transaction
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Ingest job was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+///A background ingest job, shared between the task doing the work and the status/cancel endpoints
+pub struct Job {
+    status: std::sync::Mutex<JobStatus>,
+    cancel_requested: AtomicBool,
+    events: broadcast::Sender<ProgressEvent>,
+    finished_at: OnceLock<Instant>,
+}
+
+impl Job {
+    ///Makes a new queued job with a random ID
+    pub fn new(user_id: &str) -> Self {
+        Job {
+            status: std::sync::Mutex::new(JobStatus {
+                id: Uuid::new_v4().to_string(),
+                user_id: user_id.to_string(),
+                state: JobState::Queued,
+                percent: 0,
+                episodes_processed: 0,
+                total_episodes: 0,
+                current_episode: None,
+                errors: Vec::new(),
+                report_id: None,
+                report: None,
+            }),
+            cancel_requested: AtomicBool::new(false),
+            events: broadcast::channel(256).0,
+            finished_at: OnceLock::new(),
+        }
+    }
+
+    ///Subscribes to the job's progress events
+    pub fn subscribe(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.events.subscribe()
+    }
+
+    ///Sends a progress event to every subscriber, it is fine if nobody is listening
+    pub fn emit(&self, event: ProgressEvent) {
+        let _ = self.events.send(event);
+    }
+
+    ///Gets a snapshot of the job's current status
+    pub fn status(&self) -> JobStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn id(&self) -> String {
+        self.status.lock().unwrap().id.clone()
+    }
+
+    ///Checks if the job is still queued or running
+    pub fn is_active(&self) -> bool {
+        matches!(self.status.lock().unwrap().state, JobState::Queued | JobState::Running)
+    }
+
+    ///When the job completed, failed or was cancelled, None while it is still active
+    pub fn finished_at(&self) -> Option<Instant> {
+        self.finished_at.get().copied()
+    }
+
+    ///Asks the job to stop, returns false if it has already finished
+    pub fn cancel(&self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        true
+    }
+
+    ///Returns an error if cancellation was requested, so ingestion can stop with `?`
+    pub fn check_cancelled(&self) -> Result<(), Cancelled> {
+        if self.cancel_requested.load(Ordering::SeqCst) {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_running(&self) {
+        self.status.lock().unwrap().state = JobState::Running;
+    }
+
+    ///Records which episode is being processed out of the total
+    pub fn set_progress(&self, episodes_processed: usize, total_episodes: usize, current_episode: Option<String>) {
+        let mut status = self.status.lock().unwrap();
+        status.episodes_processed = episodes_processed;
+        status.total_episodes = total_episodes;
+        status.current_episode = current_episode.clone();
+        status.percent = (episodes_processed * 100).checked_div(total_episodes).unwrap_or(0).min(100) as u8;
+        drop(status);
+        self.emit(ProgressEvent::EpisodeProgress { episodes_processed, total_episodes, current_episode });
+    }
+
+    pub fn complete(&self, report_id: Option<i64>, report: IngestReport) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Completed;
+        status.percent = 100;
+        status.current_episode = None;
+        status.report_id = report_id;
+        status.report = Some(report.clone());
+        drop(status);
+        let _ = self.finished_at.set(Instant::now());
+        self.emit(ProgressEvent::Completed { report_id, report });
+    }
+
+    pub fn fail(&self, errors: Vec<String>) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Failed;
+        status.current_episode = None;
+        status.errors = errors.clone();
+        drop(status);
+        let _ = self.finished_at.set(Instant::now());
+        self.emit(ProgressEvent::Failed { errors });
+    }
+
+    pub fn mark_cancelled(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.state = JobState::Cancelled;
+        status.current_episode = None;
+        drop(status);
+        let _ = self.finished_at.set(Instant::now());
+        self.emit(ProgressEvent::Cancelled);
+    }
+}
@@ -1,4 +1,8 @@
 pub mod api;
+pub mod archive;
 pub mod db;
 pub mod file_parser;
-pub mod models;
\ No newline at end of file
+pub mod jobs;
+pub mod models;
+pub mod speakers;
+pub mod ws;
\ No newline at end of file
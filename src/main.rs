@@ -1,11 +1,17 @@
 mod api;
+mod archive;
 mod db;
 mod file_parser;
+mod jobs;
 mod models;
+mod speakers;
+mod ws;
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use api::init_routes;
+use archive::UploadLimits;
 use dotenv::dotenv;
+use jobs::{EditRegistry, JobRegistry};
 use sqlx::SqlitePool;
 use std::collections::HashMap;
 use std::path::Path;
@@ -20,6 +26,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
     let schema_path = "schema.sql".to_string();
     let db_registry: DatabaseRegistry = Arc::new(Mutex::new(HashMap::new()));
+    let job_registry: JobRegistry = Arc::new(Mutex::new(HashMap::new()));
+    let edit_registry: EditRegistry = Arc::new(std::sync::Mutex::new(HashMap::new()));
+    let upload_limits = UploadLimits::from_env();
     if Path::new("./temp_dbs").exists() {
         if let Err(err) = fs::remove_dir_all("./temp_dbs") {
             eprintln!("Failed to clean up temp_dbs directory: {}", err);
@@ -38,7 +47,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         App::new()
             .wrap(cors)
             .app_data(web::Data::new(db_registry.clone()))
+            .app_data(web::Data::new(job_registry.clone()))
+            .app_data(web::Data::new(edit_registry.clone()))
             .app_data(web::Data::new(schema_path.clone()))
+            .app_data(web::Data::new(upload_limits.clone()))
             .configure(init_routes)
     })
 
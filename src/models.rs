@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
 
 
@@ -22,6 +23,24 @@ pub struct Episode {
     pub season_id: i64,
     pub number: i32,
     pub title: String,
+    pub air_date: Option<String>,
+    pub writers: Option<Json<Vec<String>>>,
+    pub directors: Option<Json<Vec<String>>>,
+    pub synopsis: Option<String>,
+    pub production_code: Option<String>,
+    pub metadata: Option<Json<serde_json::Value>>,
+    pub number_end: Option<i32>,
+    pub absolute_number: Option<i32>,
+    pub content_hash: Option<String>,
+    pub encoding: Option<String>,
+}
+
+//Represents whether a file is already loaded, and the episodes loaded from it
+#[derive(Clone, Debug, Serialize)]
+pub struct LoadedFile {
+    pub content_hash: String,
+    pub loaded: bool,
+    pub episodes: Vec<Episode>,
 }
 
 //Represents a single speaker
@@ -31,6 +50,57 @@ pub struct Speaker {
     pub name: String,
 }
 
+//Represents a scene of an episode, the lines from one scene heading or break to the next, with the speakers that have lines in it
+#[derive(Clone, FromRow, Debug, Deserialize, Serialize)]
+pub struct Scene {
+    pub id: i64,
+    pub episode_id: i64,
+    pub number: i32,
+    pub heading: Option<String>,
+    pub location: Option<String>,
+    pub first_line_number: i32,
+    pub last_line_number: i32,
+    pub speakers: Json<Vec<Speaker>>,
+}
+
+//Represents a speaker with the other names that uploads resolve to it
+#[derive(Clone, FromRow, Debug, Serialize)]
+pub struct SpeakerWithAliases {
+    pub id: i64,
+    pub name: String,
+    pub aliases: Json<Vec<String>>,
+}
+
+//Represents a request to rename a speaker
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenameSpeakerRequest {
+    pub name: String,
+}
+
+//Represents a request to merge several speakers into one
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeSpeakersRequest {
+    pub into: i64,
+    pub speakers: Vec<i64>,
+}
+
+//Represents a request to register an alias for a speaker
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpeakerAliasRequest {
+    pub alias: String,
+}
+
+//Represents the outcome of merging speakers
+#[derive(Clone, Debug, Serialize)]
+pub struct SpeakerMergeSummary {
+    pub speaker: SpeakerWithAliases,
+    pub speakers_merged: u64,
+    pub lines_updated: u64,
+}
+
 //Represents a single line attached to a speaker, episode, and season
 #[derive(Clone, FromRow, Debug, Deserialize, Serialize)]
 pub struct Line {
@@ -39,8 +109,42 @@ pub struct Line {
     pub episode_id: i64,
     pub speaker_id: Option<i64>,
     pub speaker_name: Option<String>,
+    pub speaker_label: Option<String>,
     pub line_number: i32,
     pub content: String,
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    pub cue_settings: Option<String>,
+    pub style: Option<String>,
+    pub parenthetical: Option<String>,
+    pub scene_heading: Option<String>,
+    pub kind: LineKind,
+    pub speakers: Json<Vec<Speaker>>,
+}
+
+//Represents what a transcript line is, so searches can leave out everything that isn't dialogue
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum LineKind {
+    #[default]
+    Dialogue,
+    StageDirection,
+    SceneHeading,
+    InlineAction,
+    Blank,
+}
+
+impl LineKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineKind::Dialogue => "dialogue",
+            LineKind::StageDirection => "stage_direction",
+            LineKind::SceneHeading => "scene_heading",
+            LineKind::InlineAction => "inline_action",
+            LineKind::Blank => "blank",
+        }
+    }
 }
 
 //Represents a search query for a specific phrase
@@ -51,6 +155,22 @@ pub struct SearchPhrasesQuery {
     pub episode: Option<i64>,
     pub speaker: Option<i64>,
     pub similar_search: Option<bool>,
+    pub kind: Option<String>,
+    pub aired_after: Option<String>,
+    pub aired_before: Option<String>,
+    #[serde(default)]
+    pub context: SearchContext,
+}
+
+//Represents which lines are returned around each search result
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchContext {
+    //The 2 lines before and after the result
+    #[default]
+    Lines,
+    //Every line of the scene the result is in
+    Scene,
 }
 
 //Represents a query to get a random line from the database
@@ -59,4 +179,269 @@ pub struct RandomLineQuery {
     pub season: Option<i64>,
     pub episode: Option<i64>,
     pub speaker: Option<i64>,
-}
\ No newline at end of file
+    pub kind: Option<String>,
+    pub aired_after: Option<String>,
+    pub aired_before: Option<String>,
+}
+
+//Represents a query to get an episode's transcript, optionally with only some kinds of lines
+#[derive(Deserialize)]
+pub struct TranscriptQuery {
+    pub kind: Option<String>,
+}
+//Represents one episode in a structured JSON/JSONL transcript upload
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TranscriptUpload {
+    pub show: Option<String>,
+    pub season: i32,
+    pub episode: i32,
+    #[serde(default)]
+    pub title: String,
+    pub lines: Vec<TranscriptUploadLine>,
+}
+
+//Represents a single line in a structured transcript upload
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TranscriptUploadLine {
+    pub speaker: Option<String>,
+    pub content: String,
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+}
+
+//Represents a CSV/TSV column, either by its header name or its 0-based index
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+//Represents which CSV/TSV columns hold each transcript field, columns left out fall back to a header with the field's name
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvColumnMapping {
+    pub season: Option<CsvColumn>,
+    pub episode: Option<CsvColumn>,
+    pub title: Option<CsvColumn>,
+    pub speaker: Option<CsvColumn>,
+    pub content: Option<CsvColumn>,
+    pub line_number: Option<CsvColumn>,
+    pub delimiter: Option<char>,
+    pub has_headers: Option<bool>,
+}
+
+//Represents how speaker names from transcript files are normalized
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeakerCase {
+    #[default]
+    Preserve,
+    Upper,
+    Lower,
+    Title,
+}
+
+//Represents the rules for telling speaker labels apart from other text before a colon, and for normalizing speaker names
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeakerRules {
+    pub max_length: usize,
+    pub pattern: Option<String>,
+    pub case: SpeakerCase,
+    pub strip_suffixes: Vec<String>,
+    pub ignore: Vec<String>,
+    pub separators: Vec<String>,
+}
+
+impl Default for SpeakerRules {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        SpeakerRules {
+            max_length: 40,
+            pattern: None,
+            case: SpeakerCase::Preserve,
+            strip_suffixes: strings(&["V.O.", "O.S.", "O.C.", "CONT'D", "CONT\u{2019}D", "CONT.", "CONTD", "OFF"]),
+            ignore: strings(&["note", "notes", "http", "https", "warning", "caution"]),
+            separators: strings(&["&", "/", ","]),
+        }
+    }
+}
+
+//Represents a manifest.json/manifest.toml at the root of an upload archive, which gives the episode details for each file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub episodes: Vec<ManifestEntry>,
+}
+
+//Represents one file listed in an upload manifest, this overrides what is parsed from the filename
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    pub file: String,
+    pub season: i32,
+    pub episode: i32,
+    pub episode_end: Option<i32>,
+    pub absolute_number: Option<i32>,
+    pub title: Option<String>,
+    pub air_date: Option<String>,
+    #[serde(default)]
+    pub writers: Vec<String>,
+    #[serde(default)]
+    pub directors: Vec<String>,
+    pub synopsis: Option<String>,
+    pub production_code: Option<String>,
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+//Represents a file from an upload that was not ingested, and why
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SkippedFile {
+    pub file: String,
+    pub reason: String,
+}
+
+//Represents a warning about a file that was still ingested, or about the upload as a whole when there is no file
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IngestWarning {
+    pub file: Option<String>,
+    pub message: String,
+}
+
+//Represents an episode created by an upload
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EpisodeReport {
+    pub season: i32,
+    pub episode: i32,
+    pub title: String,
+    pub file: String,
+    pub lines: usize,
+    pub lines_without_speaker: usize,
+    pub replaced: bool,
+    pub encoding: String,
+    pub scenes: usize,
+    pub content_hash: String,
+    pub change: EpisodeChange,
+}
+
+//Represents how an episode's file compares to the one the episode was last loaded from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EpisodeChange {
+    //The episode wasn't loaded before
+    New,
+    //The episode was loaded from a different file
+    Changed,
+    //The episode was loaded from the same file
+    Unchanged,
+}
+
+//Represents what was removed by deleting an episode or season
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeleteSummary {
+    pub episodes_deleted: u64,
+    pub lines_deleted: u64,
+    pub speakers_removed: u64,
+}
+
+//Represents several files in an upload that were for the same season + episode, only the first one is kept
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EpisodeCollision {
+    pub season: i32,
+    pub episode: i32,
+    pub kept_file: String,
+    pub skipped_files: Vec<String>,
+}
+
+//Represents the outcome of processing an upload
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct IngestReport {
+    pub mode: UploadMode,
+    pub episodes_created: usize,
+    pub episodes_replaced: usize,
+    pub total_lines: usize,
+    pub lines_without_speaker: usize,
+    pub episodes: Vec<EpisodeReport>,
+    pub skipped_files: Vec<SkippedFile>,
+    pub unchanged_files: Vec<String>,
+    pub collisions: Vec<EpisodeCollision>,
+    pub warnings: Vec<IngestWarning>,
+    pub throughput: IngestThroughput,
+}
+
+//Represents how long an upload took to load, split by stage, and how many lines and episodes were loaded per second
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct IngestThroughput {
+    pub elapsed_ms: u64,
+    pub parse_ms: u64,
+    pub insert_ms: u64,
+    pub index_ms: u64,
+    pub lines_per_second: f64,
+    pub episodes_per_second: f64,
+}
+
+//Represents whether an upload replaces the whole dataset, or merges its episodes into the existing one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadMode {
+    #[default]
+    Replace,
+    Merge,
+}
+
+//Represents an ingest report saved in the database
+#[derive(Clone, FromRow, Debug, Serialize)]
+pub struct StoredIngestReport {
+    pub id: i64,
+    pub created_at: String,
+    pub report: Json<IngestReport>,
+}
+
+//Represents a saved ingest report without its contents, for listing
+#[derive(Clone, FromRow, Debug, Serialize)]
+pub struct IngestReportSummary {
+    pub id: i64,
+    pub created_at: String,
+}
+
+//Represents where a background ingest job is in its lifecycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+//Represents the progress of a background ingest job, as returned by the job status endpoint
+#[derive(Clone, Debug, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub user_id: String,
+    pub state: JobState,
+    pub percent: u8,
+    pub episodes_processed: usize,
+    pub total_episodes: usize,
+    pub current_episode: Option<String>,
+    pub errors: Vec<String>,
+    pub report_id: Option<i64>,
+    pub report: Option<IngestReport>,
+}
+
+//Represents a progress event sent to WebSocket clients following an ingest job
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    Status { status: JobStatus },
+    FileStarted { file: String, season: i32, episode: i32, title: String },
+    EpisodeProgress { episodes_processed: usize, total_episodes: usize, current_episode: Option<String> },
+    Warning { file: Option<String>, message: String },
+    Completed { report_id: Option<i64>, report: IngestReport },
+    Failed { errors: Vec<String> },
+    Cancelled,
+}
@@ -0,0 +1,261 @@
+use crate::models::{SpeakerMergeSummary, SpeakerWithAliases};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use std::fmt;
+
+///Selects speakers with their aliases, aliases are kept by the speaker's name so they still apply after a replace upload deletes their lines
+const SPEAKER_WITH_ALIASES_SQL: &str = r#"
+    SELECT
+        s.id,
+        s.name,
+        (SELECT json_group_array(alias) FROM (
+            SELECT a.alias FROM speaker_aliases a WHERE a.name = s.name ORDER BY a.alias
+        )) AS aliases
+    FROM speakers s
+"#;
+
+///Reasons a speaker change is refused, each mapped to its own status code by the API
+#[derive(Debug)]
+pub enum SpeakerError {
+    NotFound(i64),
+    NameTaken(String),
+    AliasTaken { alias: String, name: String },
+    Invalid(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for SpeakerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpeakerError::NotFound(id) => write!(f, "Speaker {} not found", id),
+            SpeakerError::NameTaken(name) => write!(f, "A speaker named {} already exists, merge the speakers instead", name),
+            SpeakerError::AliasTaken { alias, name } => write!(f, "{} is already an alias of {}", alias, name),
+            SpeakerError::Invalid(message) => write!(f, "{}", message),
+            SpeakerError::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SpeakerError {}
+
+impl From<sqlx::Error> for SpeakerError {
+    fn from(err: sqlx::Error) -> Self {
+        SpeakerError::Database(err)
+    }
+}
+
+///Trims a speaker name or alias, refusing empty ones
+fn clean_name(name: &str, field: &str) -> Result<String, SpeakerError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(SpeakerError::Invalid(format!("{} must not be empty", field)));
+    }
+    Ok(name.to_string())
+}
+
+///Gets a speaker's name, or NotFound
+async fn speaker_name(conn: &mut SqliteConnection, speaker_id: i64) -> Result<String, SpeakerError> {
+    sqlx::query_scalar("SELECT name FROM speakers WHERE id = ?")
+        .bind(speaker_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(SpeakerError::NotFound(speaker_id))
+}
+
+///Gets the name of the speaker an alias belongs to, ignoring case
+async fn alias_owner(conn: &mut SqliteConnection, alias: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT name FROM speaker_aliases WHERE alias = ?")
+        .bind(alias)
+        .fetch_optional(conn)
+        .await
+}
+
+///Gets a speaker with its aliases
+async fn get_speaker(conn: &mut SqliteConnection, speaker_id: i64) -> Result<SpeakerWithAliases, SpeakerError> {
+    sqlx::query_as(&format!("{} WHERE s.id = ?", SPEAKER_WITH_ALIASES_SQL))
+        .bind(speaker_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(SpeakerError::NotFound(speaker_id))
+}
+
+///Lists every speaker with its aliases
+pub async fn list_speakers(pool: &SqlitePool) -> Result<Vec<SpeakerWithAliases>, sqlx::Error> {
+    sqlx::query_as(&format!("{} ORDER BY s.id", SPEAKER_WITH_ALIASES_SQL))
+        .fetch_all(pool)
+        .await
+}
+
+///Moves the lines and aliases of the source speakers to the target, keeps the source names as aliases of the target, then deletes the source speakers.
+///Returns how many lines had one of the source speakers
+async fn merge_into(
+    conn: &mut SqliteConnection,
+    target_id: i64,
+    target_name: &str,
+    source_ids: &[i64],
+) -> Result<u64, sqlx::Error> {
+    let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(DISTINCT line_id) FROM line_speakers WHERE speaker_id IN (");
+    let mut ids = query.separated(", ");
+    for source_id in source_ids {
+        ids.push_bind(*source_id);
+    }
+    query.push(")");
+    let lines_updated: i64 = query.build_query_scalar().fetch_one(&mut *conn).await?;
+
+    for source_id in source_ids {
+        let source_name: String = sqlx::query_scalar("SELECT name FROM speakers WHERE id = ?")
+            .bind(source_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        sqlx::query("UPDATE lines SET speaker_id = ?1 WHERE speaker_id = ?2")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?;
+        //A line that already has the target speaker keeps that row, and the source's row is dropped
+        sqlx::query("UPDATE OR IGNORE line_speakers SET speaker_id = ?1 WHERE speaker_id = ?2")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM line_speakers WHERE speaker_id = ?")
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("UPDATE speaker_aliases SET name = ?1 WHERE name = ?2")
+            .bind(target_name)
+            .bind(&source_name)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("INSERT OR IGNORE INTO speaker_aliases (alias, name) VALUES (?, ?)")
+            .bind(&source_name)
+            .bind(target_name)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM speakers WHERE id = ?")
+            .bind(source_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(lines_updated as u64)
+}
+
+///Renames a speaker, keeping the old name as an alias so later uploads still resolve it.
+///Neither the new name nor the old one can be an alias of another speaker, or uploads would resolve it to both
+pub async fn rename_speaker(pool: &SqlitePool, speaker_id: i64, name: &str) -> Result<SpeakerWithAliases, SpeakerError> {
+    let name = clean_name(name, "name")?;
+    let mut transaction = pool.begin().await?;
+    let old_name = speaker_name(&mut transaction, speaker_id).await?;
+    if old_name != name {
+        let taken: Option<i64> = sqlx::query_scalar("SELECT id FROM speakers WHERE name = ?")
+            .bind(&name)
+            .fetch_optional(&mut *transaction)
+            .await?;
+        if taken.is_some() {
+            return Err(SpeakerError::NameTaken(name));
+        }
+        for alias in [&name, &old_name] {
+            if let Some(owner) = alias_owner(&mut transaction, alias).await?.filter(|owner| *owner != old_name) {
+                return Err(SpeakerError::AliasTaken { alias: alias.clone(), name: owner });
+            }
+        }
+        //The speaker's own alias matching the new name isn't needed once it is the name
+        sqlx::query("DELETE FROM speaker_aliases WHERE alias = ?1 AND name = ?2")
+            .bind(&name)
+            .bind(&old_name)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("UPDATE speakers SET name = ? WHERE id = ?")
+            .bind(&name)
+            .bind(speaker_id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("UPDATE speaker_aliases SET name = ?1 WHERE name = ?2")
+            .bind(&name)
+            .bind(&old_name)
+            .execute(&mut *transaction)
+            .await?;
+        //The old name can only already be an alias of this speaker, in a different case
+        if alias_owner(&mut transaction, &old_name).await?.is_none() {
+            sqlx::query("INSERT INTO speaker_aliases (alias, name) VALUES (?, ?)")
+                .bind(&old_name)
+                .bind(&name)
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    let speaker = get_speaker(&mut transaction, speaker_id).await?;
+    transaction.commit().await?;
+    Ok(speaker)
+}
+
+///Merges several speakers into one, moving all of their lines and aliases to it
+pub async fn merge_speakers(pool: &SqlitePool, into: i64, speaker_ids: &[i64]) -> Result<SpeakerMergeSummary, SpeakerError> {
+    let mut source_ids: Vec<i64> = speaker_ids.iter().copied().filter(|id| *id != into).collect();
+    source_ids.sort_unstable();
+    source_ids.dedup();
+    if source_ids.is_empty() {
+        return Err(SpeakerError::Invalid("speakers must list at least one speaker other than the one merged into".to_string()));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let target_name = speaker_name(&mut transaction, into).await?;
+    for source_id in &source_ids {
+        speaker_name(&mut transaction, *source_id).await?;
+    }
+    let lines_updated = merge_into(&mut transaction, into, &target_name, &source_ids).await?;
+    let speaker = get_speaker(&mut transaction, into).await?;
+    transaction.commit().await?;
+    Ok(SpeakerMergeSummary { speaker, speakers_merged: source_ids.len() as u64, lines_updated })
+}
+
+///Registers an alias for a speaker, which later uploads resolve to the speaker ignoring case.
+///Speakers already named like the alias are merged into the speaker
+pub async fn add_alias(pool: &SqlitePool, speaker_id: i64, alias: &str) -> Result<SpeakerWithAliases, SpeakerError> {
+    let alias = clean_name(alias, "alias")?;
+    let mut transaction = pool.begin().await?;
+    let name = speaker_name(&mut transaction, speaker_id).await?;
+    let existing: Option<String> = sqlx::query_scalar("SELECT name FROM speaker_aliases WHERE alias = ?")
+        .bind(&alias)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    match existing {
+        Some(existing) if existing != name => return Err(SpeakerError::AliasTaken { alias, name: existing }),
+        Some(_) => {}
+        None => {
+            sqlx::query("INSERT INTO speaker_aliases (alias, name) VALUES (?, ?)")
+                .bind(&alias)
+                .bind(&name)
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+
+    let same_name: Vec<i64> = sqlx::query_scalar("SELECT id FROM speakers WHERE name = ? COLLATE NOCASE AND id != ?")
+        .bind(&alias)
+        .bind(speaker_id)
+        .fetch_all(&mut *transaction)
+        .await?;
+    if !same_name.is_empty() {
+        merge_into(&mut transaction, speaker_id, &name, &same_name).await?;
+    }
+    let speaker = get_speaker(&mut transaction, speaker_id).await?;
+    transaction.commit().await?;
+    Ok(speaker)
+}
+
+///Removes one of a speaker's aliases
+pub async fn remove_alias(pool: &SqlitePool, speaker_id: i64, alias: &str) -> Result<SpeakerWithAliases, SpeakerError> {
+    let mut transaction = pool.begin().await?;
+    let name = speaker_name(&mut transaction, speaker_id).await?;
+    let removed = sqlx::query("DELETE FROM speaker_aliases WHERE alias = ? AND name = ?")
+        .bind(alias.trim())
+        .bind(&name)
+        .execute(&mut *transaction)
+        .await?;
+    if removed.rows_affected() == 0 {
+        return Err(SpeakerError::Invalid(format!("{} is not an alias of {}", alias.trim(), name)));
+    }
+    let speaker = get_speaker(&mut transaction, speaker_id).await?;
+    transaction.commit().await?;
+    Ok(speaker)
+}
@@ -0,0 +1,78 @@
+use crate::jobs::Job;
+use crate::models::ProgressEvent;
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web_actors::ws;
+use futures_util::stream;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+///WebSocket session that forwards an ingest job's progress events to the client as JSON text messages
+pub struct JobProgressSocket {
+    job: Arc<Job>,
+}
+
+impl JobProgressSocket {
+    pub fn new(job: Arc<Job>) -> Self {
+        JobProgressSocket { job }
+    }
+
+    ///Sends an event to the client, and closes the socket once the job has finished
+    fn send_event(&self, event: &ProgressEvent, ctx: &mut ws::WebsocketContext<Self>) {
+        match serde_json::to_string(event) {
+            Ok(text) => ctx.text(text),
+            Err(err) => eprintln!("Failed to serialize progress event: {}", err),
+        }
+        let finished = match event {
+            ProgressEvent::Status { .. } => !self.job.is_active(),
+            ProgressEvent::Completed { .. } | ProgressEvent::Failed { .. } | ProgressEvent::Cancelled => true,
+            _ => false,
+        };
+        if finished {
+            ctx.close(Some(ws::CloseCode::Normal.into()));
+            ctx.stop();
+        }
+    }
+}
+
+impl Actor for JobProgressSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    ///Subscribes before sending the current status, so no event between the two is missed
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let receiver = self.job.subscribe();
+        self.send_event(&ProgressEvent::Status { status: self.job.status() }, ctx);
+        if !self.job.is_active() {
+            return;
+        }
+        let events = stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        ctx.add_stream(events);
+    }
+}
+
+impl StreamHandler<ProgressEvent> for JobProgressSocket {
+    fn handle(&mut self, event: ProgressEvent, ctx: &mut Self::Context) {
+        self.send_event(&event, ctx);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for JobProgressSocket {
+    fn handle(&mut self, message: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match message {
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(_) => ctx.stop(),
+            _ => {}
+        }
+    }
+}